@@ -5,7 +5,11 @@
 use std::any::Any;
 use std::convert::TryInto;
 use std::net::IpAddr;
+#[cfg(any(feature = "__tls", unix))]
+use std::path::Path;
 use std::sync::Arc;
+#[cfg(feature = "socks")]
+use std::sync::Mutex;
 #[cfg(feature = "cookies")]
 use std::sync::RwLock;
 use std::time::Duration;
@@ -14,16 +18,19 @@ use std::{fmt, str};
 use bytes::Bytes;
 use http::header::{
     Entry, HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
-    CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING, USER_AGENT,
+    AUTHORIZATION, CONTENT_TYPE, LOCATION, RANGE, REFERER, TRANSFER_ENCODING,
+    USER_AGENT, WWW_AUTHENTICATE,
 };
-use http::uri::Scheme;
-use http::Uri;
+use http::{Extensions, Uri};
+use hyper::client::connect::Connection;
 use hyper::client::ResponseFuture;
+use hyper::service::Service;
 #[cfg(feature = "native-tls-crate")]
 use native_tls_crate::TlsConnector;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::Delay;
 
 use log::debug;
@@ -32,14 +39,21 @@ use super::decoder::Accepts;
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
 use super::Body;
+use crate::auth::{AuthFuture, AuthProvider, Challenges, Exchange, IntoNetrc, Netrc};
 use crate::connect::{Connector, HttpConnector};
 #[cfg(feature = "cookies")]
 use crate::cookie;
-use crate::error;
+use crate::error::{self, BoxError};
+use crate::hsts;
 use crate::into_url::{expect_uri, try_uri};
-use crate::redirect::{self, remove_sensitive_headers};
+#[cfg(feature = "socks")]
+use crate::proxy::SocksIsolation;
+use crate::redirect;
+use crate::sign::{self, RequestSigner};
 #[cfg(feature = "__tls")]
-use crate::tls::TlsBackend;
+use crate::tls::{self, CertificatePins, RevocationLists, TlsBackend};
+#[cfg(feature = "rustls-tls")]
+use crate::tls::IdentityResolver;
 #[cfg(feature = "__tls")]
 use crate::{Certificate, Identity};
 use crate::{IntoUrl, Method, Proxy, StatusCode, Url};
@@ -82,6 +96,14 @@ struct Config {
     pool_max_idle_per_host: usize,
     #[cfg(feature = "__tls")]
     identity: Option<Identity>,
+    #[cfg(feature = "rustls-tls")]
+    identity_resolver: Option<IdentityResolver>,
+    #[cfg(feature = "rustls-tls")]
+    tls_session_cache_size: Option<usize>,
+    #[cfg(feature = "rustls-tls")]
+    tls_session_cache_shared: bool,
+    #[cfg(feature = "rustls-tls")]
+    tls_early_data: bool,
     proxies: Vec<Proxy>,
     auto_sys_proxy: bool,
     redirect_policy: redirect::Policy,
@@ -90,6 +112,22 @@ struct Config {
     #[cfg(feature = "__tls")]
     root_certs: Vec<Certificate>,
     #[cfg(feature = "__tls")]
+    tls_built_in_root_certs: bool,
+    #[cfg(feature = "__tls")]
+    tls_root_certs_from_env: bool,
+    #[cfg(feature = "__tls")]
+    certificate_pins: CertificatePins,
+    #[cfg(feature = "__tls")]
+    revocation_lists: RevocationLists,
+    #[cfg(feature = "__tls")]
+    tls_ocsp_stapling: bool,
+    #[cfg(feature = "__tls")]
+    min_tls_version: Option<tls::Version>,
+    #[cfg(feature = "__tls")]
+    max_tls_version: Option<tls::Version>,
+    #[cfg(feature = "__tls")]
+    tls_cipher_suites: Option<Vec<String>>,
+    #[cfg(feature = "__tls")]
     tls: TlsBackend,
     http2_only: bool,
     http1_writev: Option<bool>,
@@ -98,8 +136,15 @@ struct Config {
     http2_initial_connection_window_size: Option<u32>,
     local_address: Option<IpAddr>,
     nodelay: bool,
+    #[cfg(unix)]
+    unix_socket: Option<Arc<Path>>,
+    connector: Option<HttpConnector>,
     #[cfg(feature = "cookies")]
     cookie_store: Option<cookie::CookieStore>,
+    hsts: Option<Arc<hsts::Store>>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    netrc: Option<Arc<Netrc>>,
     trust_dns: bool,
     error: Option<crate::Error>,
 }
@@ -139,7 +184,31 @@ impl ClientBuilder {
                 #[cfg(feature = "__tls")]
                 root_certs: Vec::new(),
                 #[cfg(feature = "__tls")]
+                tls_built_in_root_certs: true,
+                #[cfg(feature = "__tls")]
+                tls_root_certs_from_env: false,
+                #[cfg(feature = "__tls")]
+                certificate_pins: CertificatePins::default(),
+                #[cfg(feature = "__tls")]
+                revocation_lists: RevocationLists::default(),
+                #[cfg(feature = "__tls")]
+                tls_ocsp_stapling: false,
+                #[cfg(feature = "__tls")]
+                min_tls_version: None,
+                #[cfg(feature = "__tls")]
+                max_tls_version: None,
+                #[cfg(feature = "__tls")]
+                tls_cipher_suites: None,
+                #[cfg(feature = "__tls")]
                 identity: None,
+                #[cfg(feature = "rustls-tls")]
+                identity_resolver: None,
+                #[cfg(feature = "rustls-tls")]
+                tls_session_cache_size: None,
+                #[cfg(feature = "rustls-tls")]
+                tls_session_cache_shared: true,
+                #[cfg(feature = "rustls-tls")]
+                tls_early_data: false,
                 #[cfg(feature = "__tls")]
                 tls: TlsBackend::default(),
                 http2_only: false,
@@ -149,9 +218,16 @@ impl ClientBuilder {
                 http2_initial_connection_window_size: None,
                 local_address: None,
                 nodelay: true,
+                #[cfg(unix)]
+                unix_socket: None,
+                connector: None,
                 trust_dns: cfg!(feature = "trust-dns"),
                 #[cfg(feature = "cookies")]
                 cookie_store: None,
+                hsts: None,
+                auth: None,
+                signer: None,
+                netrc: None,
             },
         }
     }
@@ -173,20 +249,33 @@ impl ClientBuilder {
         if config.auto_sys_proxy {
             proxies.push(Proxy::system());
         }
+        if let Some(ref netrc) = config.netrc {
+            proxies = proxies.into_iter().map(|proxy| proxy.with_netrc(netrc)).collect();
+        }
         let proxies = Arc::new(proxies);
 
+        #[cfg(feature = "__tls")]
+        let env_root_certs = if config.tls_root_certs_from_env {
+            tls::certificates_from_env()?
+        } else {
+            Vec::new()
+        };
+        #[cfg(feature = "__tls")]
+        let mut revocation_lists = config.revocation_lists;
+
         let mut connector = {
             #[cfg(feature = "__tls")]
             fn user_agent(headers: &HeaderMap) -> Option<HeaderValue> {
                 headers.get(USER_AGENT).cloned()
             }
 
-            let http = match config.trust_dns {
-                false => HttpConnector::new_gai(),
+            let http = match (config.connector, config.trust_dns) {
+                (Some(connector), _) => connector,
+                (None, false) => HttpConnector::new_gai(),
                 #[cfg(feature = "trust-dns")]
-                true => HttpConnector::new_trust_dns()?,
+                (None, true) => HttpConnector::new_trust_dns()?,
                 #[cfg(not(feature = "trust-dns"))]
-                true => unreachable!("trust-dns shouldn't be enabled unless the feature is"),
+                (None, true) => unreachable!("trust-dns shouldn't be enabled unless the feature is"),
             };
 
             #[cfg(feature = "__tls")]
@@ -202,7 +291,49 @@ impl ClientBuilder {
 
                     tls.danger_accept_invalid_certs(!config.certs_verification);
 
-                    for cert in config.root_certs {
+                    tls.disable_built_in_roots(!config.tls_built_in_root_certs);
+
+                    if let Some(min) = config.min_tls_version {
+                        let protocol = min.to_native_tls().ok_or_else(|| {
+                            crate::error::builder("minimum TLS version is not supported by native-tls")
+                        })?;
+                        tls.min_protocol_version(Some(protocol));
+                    }
+
+                    if let Some(max) = config.max_tls_version {
+                        // TLS 1.3 is the newest version native-tls knows of,
+                        // so it is the same as having no maximum.
+                        tls.max_protocol_version(max.to_native_tls());
+                    }
+
+                    if config.tls_cipher_suites.is_some() {
+                        return Err(crate::error::builder(
+                            "cipher suites cannot be configured with native-tls",
+                        ));
+                    }
+
+                    if config.tls_ocsp_stapling {
+                        return Err(crate::error::builder(
+                            "stapled OCSP responses can only be checked with rustls",
+                        ));
+                    }
+
+                    #[cfg(feature = "rustls-tls")]
+                    {
+                        if config.tls_session_cache_size.is_some()
+                            || !config.tls_session_cache_shared
+                            || config.tls_early_data
+                        {
+                            return Err(crate::error::builder(
+                                "TLS session caching and early data can only be configured with rustls",
+                            ));
+                        }
+                    }
+
+                    for cert in config.root_certs.into_iter().chain(env_root_certs) {
+                        if let Some(der) = cert.native_der() {
+                            revocation_lists.add_issuer(&der);
+                        }
                         cert.add_to_native_tls(&mut tls);
                     }
 
@@ -225,6 +356,24 @@ impl ClientBuilder {
                 },
                 #[cfg(feature = "native-tls")]
                 TlsBackend::BuiltNativeTls(conn) => {
+                    if config.tls_ocsp_stapling {
+                        return Err(crate::error::builder(
+                            "stapled OCSP responses can only be checked with rustls",
+                        ));
+                    }
+
+                    #[cfg(feature = "rustls-tls")]
+                    {
+                        if config.tls_session_cache_size.is_some()
+                            || !config.tls_session_cache_shared
+                            || config.tls_early_data
+                        {
+                            return Err(crate::error::builder(
+                                "TLS session caching and early data can only be configured with rustls",
+                            ));
+                        }
+                    }
+
                     Connector::from_built_default_tls(
                         http,
                         conn,
@@ -234,7 +383,17 @@ impl ClientBuilder {
                         config.nodelay)
                 },
                 #[cfg(feature = "rustls-tls")]
-                TlsBackend::BuiltRustls(conn) => {
+                TlsBackend::BuiltRustls(mut conn) => {
+                    if config.tls_ocsp_stapling {
+                        return Err(crate::error::builder(
+                            "stapled OCSP responses cannot be checked with a preconfigured rustls backend",
+                        ));
+                    }
+
+                    if let Some(size) = config.tls_session_cache_size {
+                        conn.set_persistence(tls::rustls_session_cache(size));
+                    }
+
                     Connector::new_rustls_tls(
                         http,
                         conn,
@@ -253,15 +412,42 @@ impl ClientBuilder {
                     } else {
                         tls.set_protocols(&["h2".into(), "http/1.1".into()]);
                     }
-                    tls.root_store
-                        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+                    if config.tls_built_in_root_certs {
+                        tls.root_store
+                            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+                    }
 
                     if !config.certs_verification {
                         tls.dangerous()
                             .set_certificate_verifier(Arc::new(NoVerifier));
+                    } else if config.tls_ocsp_stapling {
+                        tls.dangerous()
+                            .set_certificate_verifier(Arc::new(tls::OcspVerifier::new()));
+                    }
+
+                    let (min, max) = (config.min_tls_version, config.max_tls_version);
+                    tls.versions.retain(|version| match tls::Version::from_rustls(*version) {
+                        Some(version) => {
+                            min.into_iter().all(|min| version >= min)
+                                && max.into_iter().all(|max| version <= max)
+                        }
+                        None => false,
+                    });
+                    if tls.versions.is_empty() {
+                        return Err(crate::error::builder(
+                            "no TLS version supported by rustls is within the configured range",
+                        ));
+                    }
+
+                    if let Some(ref suites) = config.tls_cipher_suites {
+                        tls.ciphersuites = tls::rustls_cipher_suites(suites)?;
+                    }
+
+                    if let Some(size) = config.tls_session_cache_size {
+                        tls.set_persistence(tls::rustls_session_cache(size));
                     }
 
-                    for cert in config.root_certs {
+                    for cert in config.root_certs.into_iter().chain(env_root_certs) {
                         cert.add_to_rustls(&mut tls)?;
                     }
 
@@ -269,6 +455,8 @@ impl ClientBuilder {
                         id.add_to_rustls(&mut tls)?;
                     }
 
+                    revocation_lists.add_trust_anchors(&tls.root_store);
+
                     Connector::new_rustls_tls(
                         http,
                         tls,
@@ -295,6 +483,31 @@ impl ClientBuilder {
 
         connector.set_timeout(config.connect_timeout);
         connector.set_verbose(config.connection_verbose);
+        #[cfg(feature = "__tls")]
+        connector.set_certificate_pins(config.certificate_pins);
+        #[cfg(feature = "__tls")]
+        connector.set_revocation_lists(revocation_lists);
+        #[cfg(feature = "__tls")]
+        connector.build_proxy_tls()?;
+        #[cfg(feature = "rustls-tls")]
+        {
+            if let Some(resolver) = config.identity_resolver {
+                connector.set_identity_resolver(resolver)?;
+            }
+            if !config.tls_session_cache_shared {
+                connector.separate_proxy_session_cache(
+                    config
+                        .tls_session_cache_size
+                        .unwrap_or(tls::DEFAULT_SESSION_CACHE_SIZE),
+                );
+            }
+        }
+        #[cfg(unix)]
+        {
+            if let Some(path) = config.unix_socket {
+                connector.set_unix_socket(path);
+            }
+        }
 
         let mut builder = hyper::Client::builder();
         if config.http2_only {
@@ -321,22 +534,40 @@ impl ClientBuilder {
             builder.http1_title_case_headers(true);
         }
 
-        let hyper_client = builder.build(connector);
+        #[cfg(feature = "rustls-tls")]
+        let hyper_early_data = if config.tls_early_data {
+            connector.early_data().map(|connector| builder.build(connector))
+        } else {
+            None
+        };
 
-        let proxies_maybe_http_auth = proxies.iter().any(|p| p.maybe_has_http_auth());
+        #[cfg(feature = "socks")]
+        let isolated = IsolatedClients {
+            builder: builder.clone(),
+            connector: connector.clone(),
+            clients: Mutex::new(Vec::new()),
+        };
+        let hyper_client = builder.build(connector);
 
         Ok(Client {
             inner: Arc::new(ClientRef {
                 accepts: config.accepts,
                 #[cfg(feature = "cookies")]
                 cookie_store: config.cookie_store.map(RwLock::new),
+                hsts: config.hsts,
                 hyper: hyper_client,
+                #[cfg(feature = "rustls-tls")]
+                hyper_early_data,
+                #[cfg(feature = "socks")]
+                isolated,
                 headers: config.headers,
                 redirect_policy: config.redirect_policy,
                 referer: config.referer,
                 request_timeout: config.timeout,
                 proxies,
-                proxies_maybe_http_auth,
+                auth: config.auth,
+                signer: config.signer,
+                netrc: config.netrc,
             }),
         })
     }
@@ -444,6 +675,31 @@ impl ClientBuilder {
         self
     }
 
+    /// Enable HTTP Strict Transport Security for the client.
+    ///
+    /// Hosts that send a `Strict-Transport-Security` header over HTTPS are
+    /// remembered, and later `http://` requests to them, including redirect
+    /// targets, are sent over `https://` instead.
+    ///
+    /// By default, no HSTS store is used.
+    pub fn hsts(mut self, enable: bool) -> ClientBuilder {
+        self.config.hsts = if enable {
+            Some(Arc::new(hsts::Store::new()))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Use a specific HSTS store for the client.
+    ///
+    /// This allows preloading hosts, persisting the store, or sharing it
+    /// between clients. See the [`hsts`](crate::hsts) module.
+    pub fn hsts_store(mut self, store: Arc<hsts::Store>) -> ClientBuilder {
+        self.config.hsts = Some(store);
+        self
+    }
+
     /// Enable auto gzip decompression by checking the `Content-Encoding` response header.
     ///
     /// If auto gzip decompression is turned on:
@@ -540,6 +796,69 @@ impl ClientBuilder {
         self
     }
 
+    // Authentication options
+
+    /// Authenticate requests with `provider`, like an `auth::OAuth2`.
+    ///
+    /// Requests without an `Authorization` header get the one of the
+    /// provider. When a server answers with `401 Unauthorized`, the request
+    /// is sent once more: on a new connection authenticated by exchanging
+    /// tokens with the server, if the provider supports one of its
+    /// challenges, or else with the `Authorization` header the provider
+    /// refreshed, if any. The refreshed header is only sent when the server
+    /// has the origin of the request the client was given, not after a
+    /// redirect to another one. Requests with bodies that can't be sent
+    /// again are not retried.
+    ///
+    /// See `Proxy::auth` to authenticate with proxies.
+    pub fn auth(mut self, provider: Arc<dyn AuthProvider>) -> ClientBuilder {
+        self.config.auth = Some(provider);
+        self
+    }
+
+    /// Authenticate with the credentials of a `.netrc` file, like curl.
+    ///
+    /// `true` reads the file named by the `NETRC` environment variable, or
+    /// else `.netrc` in the home directory (`_netrc` on Windows), if it
+    /// exists. A path reads that file instead, and `false` no file.
+    ///
+    /// Requests without an `Authorization` header, and proxies without
+    /// credentials, get Basic authentication with the `login` and `password`
+    /// of the `machine` entry for their host, or else of the `default` entry.
+    /// Redirects first remove the `Authorization` header like the redirect
+    /// policy's `SensitiveHeaders` say, then look up the credentials of the
+    /// next host.
+    ///
+    /// Building the `Client` fails if the file cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .netrc(true)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn netrc<N: IntoNetrc>(mut self, netrc: N) -> ClientBuilder {
+        match netrc.into_netrc() {
+            Ok(netrc) => self.config.netrc = netrc.map(Arc::new),
+            Err(err) => self.config.error = Some(err),
+        }
+        self
+    }
+
+    /// Sign requests with `signer`, like a `sign::AwsSigV4`.
+    ///
+    /// Requests are signed right before they are sent, once the default
+    /// headers and cookies were added, and signed again when following
+    /// redirects. Failing to sign a request fails it.
+    pub fn signer(mut self, signer: Arc<dyn RequestSigner>) -> ClientBuilder {
+        self.config.signer = Some(signer);
+        self
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -712,6 +1031,57 @@ impl ClientBuilder {
         self
     }
 
+    /// Use a custom connector to open connections, instead of TCP.
+    ///
+    /// The connector is called with the URI of the server, or of the proxy
+    /// if one applies, and stands in for DNS and TCP: TLS, HTTP proxies and
+    /// the connect timeout are still applied on top of the connections it
+    /// returns. SOCKS proxies bypass it.
+    ///
+    /// This allows running requests over in-memory streams in tests, over
+    /// vsock, or through an SSH tunnel.
+    pub fn connector<C>(mut self, connector: C) -> ClientBuilder
+    where
+        C: Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: AsyncRead + AsyncWrite + Connection + Send + Sync + Unpin + 'static,
+        C::Error: Into<BoxError>,
+        C::Future: Send + 'static,
+    {
+        self.config.connector = Some(HttpConnector::new_custom(connector));
+        self
+    }
+
+    /// Send all requests over the Unix domain socket at `path`.
+    ///
+    /// Connections skip DNS, proxies and TLS. Request URLs still need an
+    /// `http` scheme and a host, which is only used for the `Host` header,
+    /// so `http://localhost/containers/json` works for the Docker daemon.
+    /// Requests to `https` URLs fail.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .unix_socket("/var/run/docker.sock")
+    ///     .build()?;
+    /// let containers = client
+    ///     .get("http://localhost/containers/json")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This is only available on Unix platforms.
+    #[cfg(unix)]
+    pub fn unix_socket<P: AsRef<Path>>(mut self, path: P) -> ClientBuilder {
+        self.config.unix_socket = Some(Arc::from(path.as_ref()));
+        self
+    }
+
     // TLS options
 
     /// Add a custom root certificate.
@@ -729,124 +1099,471 @@ impl ClientBuilder {
         self
     }
 
-    /// Sets the identity to be used for client certificate authentication.
+    /// Add the certificates of all PEM files in a directory as custom root
+    /// certificates.
+    ///
+    /// This works with plain directories of `.pem` or `.crt` files as well as
+    /// OpenSSL style hashed directories. Files that do not contain PEM
+    /// encoded certificates are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails if the directory cannot be read.
     ///
     /// # Optional
     ///
-    /// This requires the optional `native-tls` or `rustls-tls` feature to be
-    /// enabled.
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
     #[cfg(feature = "__tls")]
-    pub fn identity(mut self, identity: Identity) -> ClientBuilder {
-        self.config.identity = Some(identity);
+    pub fn add_root_certificates_from_dir<P: AsRef<Path>>(mut self, dir: P) -> ClientBuilder {
+        match tls::certificates_from_dir(dir.as_ref()) {
+            Ok(certs) => self.config.root_certs.extend(certs),
+            Err(err) => self.config.error = Some(err),
+        }
         self
     }
 
-    /// Controls the use of hostname verification.
-    ///
-    /// Defaults to `false`.
+    /// Controls the use of the root certificates built into the TLS backend.
     ///
-    /// # Warning
+    /// Defaults to `true`. When disabled, only the certificates added with
+    /// `add_root_certificate` and the like are trusted.
     ///
-    /// You should think very carefully before you use this method. If
-    /// hostname verification is not used, any valid certificate for any
-    /// site will be trusted for use from any other. This introduces a
-    /// significant vulnerability to man-in-the-middle attacks.
+    /// With `native-tls` these are the certificates of the system, and with
+    /// `rustls-tls` the Mozilla roots of `webpki-roots`.
     ///
     /// # Optional
     ///
-    /// This requires the optional `native-tls` feature to be enabled.
-    #[cfg(feature = "native-tls")]
-    pub fn danger_accept_invalid_hostnames(
-        mut self,
-        accept_invalid_hostname: bool,
-    ) -> ClientBuilder {
-        self.config.hostname_verification = !accept_invalid_hostname;
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_built_in_root_certs(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_built_in_root_certs = enable;
         self
     }
 
-    /// Controls the use of certificate validation.
+    /// Trust the root certificates named by the `SSL_CERT_FILE` and
+    /// `SSL_CERT_DIR` environment variables.
     ///
-    /// Defaults to `false`.
+    /// Defaults to `false`. When enabled, `SSL_CERT_FILE` is read as a PEM
+    /// bundle and `SSL_CERT_DIR` as a list of directories, like with
+    /// `add_root_certificates_from_dir`, when the `Client` is built.
     ///
-    /// # Warning
+    /// # Errors
     ///
-    /// You should think very carefully before using this method. If
-    /// invalid certificates are trusted, *any* certificate for *any* site
-    /// will be trusted for use. This includes expired certificates. This
-    /// introduces significant vulnerabilities, and should only be used
-    /// as a last resort.
+    /// Building the `Client` fails if a file or directory named by the
+    /// variables cannot be read.
     ///
     /// # Optional
     ///
     /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
     /// feature to be enabled.
     #[cfg(feature = "__tls")]
-    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> ClientBuilder {
-        self.config.certs_verification = !accept_invalid_certs;
+    pub fn tls_root_certs_from_env(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_root_certs_from_env = enable;
         self
     }
 
-    /// Force using the native TLS backend.
+    /// Pin the certificate of a host to the SHA-256 hash of its public key.
     ///
-    /// Since multiple TLS backends can be optionally enabled, this option will
-    /// force the `native-tls` backend to be used for this `Client`.
+    /// `host_pattern` is either a host name, or `*.` followed by a domain to
+    /// match exactly one subdomain label, like `*.example.com`. The hash is
+    /// the base64 encoded SHA-256 digest of the DER encoded
+    /// `SubjectPublicKeyInfo` of a certificate in the chain the server
+    /// presents, and may be prefixed with `sha256/`. Pinning the key of an
+    /// intermediate or root certificate keeps working when the server's own
+    /// certificate is replaced. Only the end-entity certificate is available
+    /// from the `native-tls` backend, so with it only that one can be pinned.
+    ///
+    /// This can be called several times for the same host, for example to
+    /// add a backup pin. A connection to a host with pins fails unless a
+    /// certificate of its chain matches at least one of them, in addition to
+    /// the usual certificate verification. Such errors can be detected with
+    /// [`Error::is_certificate_pin_mismatch`](crate::Error::is_certificate_pin_mismatch).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn build() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .pin_certificate("api.example.com", "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")
+    ///     .build()?;
+    /// # drop(client);
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// # Optional
     ///
-    /// This requires the optional `native-tls` feature to be enabled.
-    #[cfg(feature = "native-tls")]
-    pub fn use_native_tls(mut self) -> ClientBuilder {
-        self.config.tls = TlsBackend::Default;
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn pin_certificate(mut self, host_pattern: &str, sha256_spki: &str) -> ClientBuilder {
+        if let Err(err) = self.config.certificate_pins.add(host_pattern, sha256_spki) {
+            self.config.error = Some(err);
+        }
         self
     }
 
-    /// Force using the Rustls TLS backend.
+    /// Add a certificate revocation list to check server certificates
+    /// against.
     ///
-    /// Since multiple TLS backends can be optionally enabled, this option will
-    /// force the `rustls` backend to be used for this `Client`.
+    /// A connection fails if the server's certificate is listed as revoked
+    /// by a list from its issuer. With rustls every certificate of the
+    /// presented chain is checked, native-tls only exposes the end-entity
+    /// certificate. The lists are not refreshed by the `Client`.
+    ///
+    /// A list is only trusted if its signature verifies with the key of its
+    /// issuer, and a connection fails once the list is past its `nextUpdate`
+    /// time. With native-tls, the issuer must be added with
+    /// `add_root_certificate`; rustls also accepts its trust anchors and the
+    /// certificates presented by the server.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn build() -> Result<(), Box<std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my_crl.pem")?
+    ///     .read_to_end(&mut buf)?;
+    /// let crl = reqwest::tls::CertificateRevocationList::from_pem(&buf)?;
+    /// let client = reqwest::Client::builder()
+    ///     .add_crl(crl)
+    ///     .build()?;
+    /// # drop(client);
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// # Optional
     ///
-    /// This requires the optional `rustls-tls` feature to be enabled.
-    #[cfg(feature = "rustls-tls")]
-    pub fn use_rustls_tls(mut self) -> ClientBuilder {
-        self.config.tls = TlsBackend::Rustls;
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn add_crl(mut self, crl: tls::CertificateRevocationList) -> ClientBuilder {
+        self.config.revocation_lists.add(crl);
         self
     }
 
-    /// Use a preconfigured TLS backend.
+    /// Controls the checking of OCSP responses stapled to the TLS handshake.
     ///
-    /// If the passed `Any` argument is not a TLS backend that reqwest
-    /// understands, the `ClientBuilder` will error when calling `build`.
+    /// When enabled, a connection fails if the server staples a response
+    /// that reports its certificate as revoked, or a response that is
+    /// invalid or expired. Servers that staple no response are still
+    /// accepted, as are responses with an unknown status.
     ///
-    /// # Advanced
+    /// Defaults to `false`.
     ///
-    /// This is an advanced option, and can be somewhat brittle. Usage requires
-    /// keeping the preconfigured TLS argument version in sync with reqwest,
-    /// since version mismatches will result in an "unknown" TLS backend.
+    /// # Errors
     ///
-    /// If possible, it's preferable to use the methods on `ClientBuilder`
-    /// to configure reqwest's TLS.
+    /// Building the `Client` fails if this is enabled with a TLS backend
+    /// other than the built-in rustls one. The check is skipped if
+    /// certificate verification is disabled with
+    /// `danger_accept_invalid_certs`.
     ///
     /// # Optional
     ///
-    /// This requires one of the optional features `native-tls` or
-    /// `rustls-tls` to be enabled.
-    #[cfg(any(
-        feature = "native-tls",
-        feature = "rustls-tls",
-    ))]
-    pub fn use_preconfigured_tls(mut self, tls: impl Any) -> ClientBuilder {
-        let mut tls = Some(tls);
-        #[cfg(feature = "native-tls")]
-        {
-            if let Some(conn) = (&mut tls as &mut dyn Any).downcast_mut::<Option<native_tls_crate::TlsConnector>>() {
-                let tls = conn.take().expect("is definitely Some");
-                let tls = crate::tls::TlsBackend::BuiltNativeTls(tls);
-                self.config.tls = tls;
-                return self;
-            }
-        }
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_ocsp_stapling(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_ocsp_stapling = enable;
+        self
+    }
+
+    /// Set the minimum required TLS version for connections.
+    ///
+    /// By default the TLS backend's own default minimum version is used.
+    ///
+    /// # Errors
+    ///
+    /// A value of `tls::Version::TLS_1_3` will cause an error with the
+    /// `native-tls` backend, which does not support setting it.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn min_tls_version(mut self, version: tls::Version) -> ClientBuilder {
+        self.config.min_tls_version = Some(version);
+        self
+    }
+
+    /// Set the maximum allowed TLS version for connections.
+    ///
+    /// By default there is no maximum.
+    ///
+    /// # Errors
+    ///
+    /// With the `rustls-tls` backend, which only supports TLS 1.2 and 1.3,
+    /// building the `Client` fails if no supported version is within the
+    /// configured range.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn max_tls_version(mut self, version: tls::Version) -> ClientBuilder {
+        self.config.max_tls_version = Some(version);
+        self
+    }
+
+    /// Restrict the cipher suites that may be negotiated.
+    ///
+    /// Suites are given by their IANA names, like
+    /// `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256` or `TLS_AES_128_GCM_SHA256`.
+    /// Names the TLS backend does not implement are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails if none of the suites are supported, or
+    /// with the `native-tls` backend, which cannot configure cipher suites.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_cipher_suites<I>(mut self, suites: I) -> ClientBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.config.tls_cipher_suites = Some(suites.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the identity to be used for client certificate authentication.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `native-tls` or `rustls-tls` feature to be
+    /// enabled.
+    #[cfg(feature = "__tls")]
+    pub fn identity(mut self, identity: Identity) -> ClientBuilder {
+        self.config.identity = Some(identity);
+        self
+    }
+
+    /// Choose the client certificate for each server that asks for one.
+    ///
+    /// The resolver is called with the host name of the server during the
+    /// handshake. When it returns `None`, the identity set with
+    /// `ClientBuilder::identity` is used, if any.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn build() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .use_rustls_tls()
+    ///     .identity_resolver(|host| {
+    ///         if host.ends_with(".internal.example") {
+    ///             let pem = std::fs::read("internal.pem").ok()?;
+    ///             reqwest::Identity::from_pem(&pem).ok()
+    ///         } else {
+    ///             None
+    ///         }
+    ///     })
+    ///     .build()?;
+    /// # drop(client);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails when the `native-tls` backend is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn identity_resolver<F>(mut self, resolver: F) -> ClientBuilder
+    where
+        F: Fn(&str) -> Option<Identity> + Send + Sync + 'static,
+    {
+        self.config.identity_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Sets how many TLS sessions are kept for resumption.
+    ///
+    /// Resuming a session skips most of the handshake with a server that was
+    /// connected to before. Sessions are cached per server name, and `0`
+    /// disables resumption. Defaults to 32.
+    ///
+    /// Resumption is always disabled when certificate pins or revocation
+    /// lists are configured, as resumed sessions don't carry the server's
+    /// certificates to check.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails when the `native-tls` backend is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_session_cache_size(mut self, size: usize) -> ClientBuilder {
+        self.config.tls_session_cache_size = Some(size);
+        self
+    }
+
+    /// Controls whether connections tunneled through proxies share the TLS
+    /// session cache of direct connections.
+    ///
+    /// When disabled, connections through proxies get a cache of their own
+    /// with the size set by `tls_session_cache_size`. Defaults to `true`.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails when the `native-tls` backend is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_session_cache_shared(mut self, shared: bool) -> ClientBuilder {
+        self.config.tls_session_cache_shared = shared;
+        self
+    }
+
+    /// Controls sending requests as TLS 1.3 early data ("0-RTT").
+    ///
+    /// When enabled, a new direct connection that resumes a session sends
+    /// its first request together with the handshake, saving a round trip.
+    /// Early data can be replayed by an attacker, so it is only used for
+    /// safe requests, such as `GET` and `HEAD`. These connections are kept
+    /// in a pool separate from the connections of other requests.
+    ///
+    /// The server must accept early data for the round trip to be saved,
+    /// otherwise the request is sent again after the handshake.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails when the `native-tls` backend is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_early_data(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_early_data = enable;
+        self
+    }
+
+    /// Controls the use of hostname verification.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before you use this method. If
+    /// hostname verification is not used, any valid certificate for any
+    /// site will be trusted for use from any other. This introduces a
+    /// significant vulnerability to man-in-the-middle attacks.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `native-tls` feature to be enabled.
+    #[cfg(feature = "native-tls")]
+    pub fn danger_accept_invalid_hostnames(
+        mut self,
+        accept_invalid_hostname: bool,
+    ) -> ClientBuilder {
+        self.config.hostname_verification = !accept_invalid_hostname;
+        self
+    }
+
+    /// Controls the use of certificate validation.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before using this method. If
+    /// invalid certificates are trusted, *any* certificate for *any* site
+    /// will be trusted for use. This includes expired certificates. This
+    /// introduces significant vulnerabilities, and should only be used
+    /// as a last resort.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> ClientBuilder {
+        self.config.certs_verification = !accept_invalid_certs;
+        self
+    }
+
+    /// Force using the native TLS backend.
+    ///
+    /// Since multiple TLS backends can be optionally enabled, this option will
+    /// force the `native-tls` backend to be used for this `Client`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `native-tls` feature to be enabled.
+    #[cfg(feature = "native-tls")]
+    pub fn use_native_tls(mut self) -> ClientBuilder {
+        self.config.tls = TlsBackend::Default;
+        self
+    }
+
+    /// Force using the Rustls TLS backend.
+    ///
+    /// Since multiple TLS backends can be optionally enabled, this option will
+    /// force the `rustls` backend to be used for this `Client`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn use_rustls_tls(mut self) -> ClientBuilder {
+        self.config.tls = TlsBackend::Rustls;
+        self
+    }
+
+    /// Use a preconfigured TLS backend.
+    ///
+    /// If the passed `Any` argument is not a TLS backend that reqwest
+    /// understands, the `ClientBuilder` will error when calling `build`.
+    ///
+    /// # Advanced
+    ///
+    /// This is an advanced option, and can be somewhat brittle. Usage requires
+    /// keeping the preconfigured TLS argument version in sync with reqwest,
+    /// since version mismatches will result in an "unknown" TLS backend.
+    ///
+    /// If possible, it's preferable to use the methods on `ClientBuilder`
+    /// to configure reqwest's TLS.
+    ///
+    /// # Optional
+    ///
+    /// This requires one of the optional features `native-tls` or
+    /// `rustls-tls` to be enabled.
+    #[cfg(any(
+        feature = "native-tls",
+        feature = "rustls-tls",
+    ))]
+    pub fn use_preconfigured_tls(mut self, tls: impl Any) -> ClientBuilder {
+        let mut tls = Some(tls);
+        #[cfg(feature = "native-tls")]
+        {
+            if let Some(conn) = (&mut tls as &mut dyn Any).downcast_mut::<Option<native_tls_crate::TlsConnector>>() {
+                let tls = conn.take().expect("is definitely Some");
+                let tls = crate::tls::TlsBackend::BuiltNativeTls(tls);
+                self.config.tls = tls;
+                return self;
+            }
+        }
         #[cfg(feature = "rustls-tls")]
         {
             if let Some(conn) = (&mut tls as &mut dyn Any).downcast_mut::<Option<rustls::ClientConfig>>() {
@@ -1010,26 +1727,52 @@ impl Client {
     }
 
     pub(super) fn execute_request(&self, req: Request) -> Pending {
-        let (method, url, mut headers, body, timeout) = req.pieces();
-        if url.scheme() != "http" && url.scheme() != "https" {
-            return Pending::new_err(error::url_bad_scheme(url));
+        if let Some(ref provider) = self.inner.auth {
+            if !req.headers().contains_key(AUTHORIZATION) {
+                let authorization = provider.authorization(req.url());
+                return Pending {
+                    inner: PendingInner::Authorizing(AuthorizingRequest {
+                        client: self.clone(),
+                        req: Some(req),
+                        authorization,
+                    }),
+                };
+            }
+        }
+        self.send_request(req)
+    }
+
+    fn send_request(&self, mut req: Request) -> Pending {
+        if req.url().scheme() != "http" && req.url().scheme() != "https" {
+            return Pending::new_err(error::url_bad_scheme(req.url().clone()));
+        }
+
+        if let Some(hsts) = self.inner.hsts.as_ref() {
+            if hsts.upgrade(req.url_mut()) {
+                debug!("HSTS upgraded request to '{}'", req.url());
+            }
         }
 
         // insert default headers in the request headers
         // without overwriting already appended headers.
         for (key, value) in &self.inner.headers {
-            if let Entry::Vacant(entry) = headers.entry(key) {
+            if let Entry::Vacant(entry) = req.headers_mut().entry(key) {
                 entry.insert(value.clone());
             }
         }
 
+        if let Some(authorization) = self.inner.netrc_authorization(req.headers(), req.url()) {
+            req.headers_mut().insert(AUTHORIZATION, authorization);
+        }
+
         // Add cookies from the cookie store.
         #[cfg(feature = "cookies")]
         {
             if let Some(cookie_store_wrapper) = self.inner.cookie_store.as_ref() {
-                if headers.get(crate::header::COOKIE).is_none() {
+                if req.headers().get(crate::header::COOKIE).is_none() {
                     let cookie_store = cookie_store_wrapper.read().unwrap();
-                    add_cookie_header(&mut headers, &cookie_store, &url);
+                    let url = req.url().clone();
+                    add_cookie_header(req.headers_mut(), &cookie_store, &url);
                 }
             }
         }
@@ -1037,11 +1780,19 @@ impl Client {
         let accept_encoding = self.inner.accepts.as_str();
 
         if let Some(accept_encoding) = accept_encoding {
+            let headers = req.headers_mut();
             if !headers.contains_key(ACCEPT_ENCODING) && !headers.contains_key(RANGE) {
                 headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(accept_encoding));
             }
         }
 
+        if let Some(ref signer) = self.inner.signer {
+            if let Err(err) = sign::sign(&**signer, &mut req) {
+                return Pending::new_err(error::request(err).with_url(req.url().clone()));
+            }
+        }
+
+        let (method, url, headers, body, timeout, extensions) = req.pieces();
         let uri = expect_uri(&url);
 
         let (reusable, body) = match body {
@@ -1052,8 +1803,6 @@ impl Client {
             None => (None, Body::empty()),
         };
 
-        self.proxy_auth(&uri, &mut headers);
-
         let mut req = hyper::Request::builder()
             .method(method.clone())
             .uri(uri)
@@ -1066,7 +1815,7 @@ impl Client {
 
         *req.headers_mut() = headers.clone();
 
-        let in_flight = self.inner.hyper.request(req);
+        let in_flight = self.inner.hyper_for(&method, &extensions).request(req);
 
         Pending {
             inner: PendingInner::Request(PendingRequest {
@@ -1074,43 +1823,20 @@ impl Client {
                 url,
                 headers,
                 body: reusable,
+                extensions,
 
                 urls: Vec::new(),
+                history: Vec::new(),
 
                 client: self.inner.clone(),
 
                 in_flight,
                 timeout,
+                pending_redirect: None,
+                pending_auth: None,
             }),
         }
     }
-
-    fn proxy_auth(&self, dst: &Uri, headers: &mut HeaderMap) {
-        if !self.inner.proxies_maybe_http_auth {
-            return;
-        }
-
-        // Only set the header here if the destination scheme is 'http',
-        // since otherwise, the header will be included in the CONNECT tunnel
-        // request instead.
-        if dst.scheme() != Some(&Scheme::HTTP) {
-            return;
-        }
-
-        if headers.contains_key(PROXY_AUTHORIZATION) {
-            return;
-        }
-
-        for proxy in self.inner.proxies.iter() {
-            if proxy.is_match(dst) {
-                if let Some(header) = proxy.http_basic_auth(dst) {
-                    headers.insert(PROXY_AUTHORIZATION, header);
-                }
-
-                break;
-            }
-        }
-    }
 }
 
 impl fmt::Debug for Client {
@@ -1141,6 +1867,10 @@ impl Config {
             }
         }
 
+        if self.hsts.is_some() {
+            f.field("hsts", &true);
+        }
+
         f.field("accepts", &self.accepts);
 
         if !self.proxies.is_empty() {
@@ -1155,6 +1885,18 @@ impl Config {
             f.field("referer", &true);
         }
 
+        if self.auth.is_some() {
+            f.field("auth", &true);
+        }
+
+        if self.signer.is_some() {
+            f.field("signer", &true);
+        }
+
+        if self.netrc.is_some() {
+            f.field("netrc", &true);
+        }
+
         f.field("default_headers", &self.headers);
 
         if self.http1_title_case_headers {
@@ -1181,6 +1923,17 @@ impl Config {
             f.field("tcp_nodelay", &true);
         }
 
+        #[cfg(unix)]
+        {
+            if let Some(ref path) = self.unix_socket {
+                f.field("unix_socket", path);
+            }
+        }
+
+        if self.connector.is_some() {
+            f.field("connector", &true);
+        }
+
         #[cfg(feature = "native-tls")]
         {
             if !self.hostname_verification {
@@ -1193,6 +1946,57 @@ impl Config {
             if !self.certs_verification {
                 f.field("danger_accept_invalid_certs", &true);
             }
+
+            if !self.tls_built_in_root_certs {
+                f.field("tls_built_in_root_certs", &false);
+            }
+
+            if self.tls_root_certs_from_env {
+                f.field("tls_root_certs_from_env", &true);
+            }
+
+            if !self.certificate_pins.is_empty() {
+                f.field("certificate_pins", &self.certificate_pins);
+            }
+
+            if !self.revocation_lists.is_empty() {
+                f.field("revocation_lists", &self.revocation_lists);
+            }
+
+            if self.tls_ocsp_stapling {
+                f.field("tls_ocsp_stapling", &true);
+            }
+
+            if let Some(ref min) = self.min_tls_version {
+                f.field("min_tls_version", min);
+            }
+
+            if let Some(ref max) = self.max_tls_version {
+                f.field("max_tls_version", max);
+            }
+
+            if let Some(ref suites) = self.tls_cipher_suites {
+                f.field("tls_cipher_suites", suites);
+            }
+        }
+
+        #[cfg(feature = "rustls-tls")]
+        {
+            if self.identity_resolver.is_some() {
+                f.field("identity_resolver", &true);
+            }
+
+            if let Some(size) = self.tls_session_cache_size {
+                f.field("tls_session_cache_size", &size);
+            }
+
+            if !self.tls_session_cache_shared {
+                f.field("tls_session_cache_shared", &false);
+            }
+
+            if self.tls_early_data {
+                f.field("tls_early_data", &true);
+            }
         }
 
         #[cfg(all(feature = "native-tls-crate", feature = "rustls-tls"))]
@@ -1206,16 +2010,114 @@ struct ClientRef {
     accepts: Accepts,
     #[cfg(feature = "cookies")]
     cookie_store: Option<RwLock<cookie::CookieStore>>,
+    hsts: Option<Arc<hsts::Store>>,
     headers: HeaderMap,
     hyper: HyperClient,
+    #[cfg(feature = "rustls-tls")]
+    hyper_early_data: Option<HyperClient>,
+    #[cfg(feature = "socks")]
+    isolated: IsolatedClients,
     redirect_policy: redirect::Policy,
     referer: bool,
     request_timeout: Option<Duration>,
     proxies: Arc<Vec<Proxy>>,
-    proxies_maybe_http_auth: bool,
+    auth: Option<Arc<dyn AuthProvider>>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    netrc: Option<Arc<Netrc>>,
+}
+
+/// How many of the most recently used isolation credentials keep their
+/// pools, as each request may well use new ones.
+#[cfg(feature = "socks")]
+const MAX_ISOLATED_CLIENTS: usize = 32;
+
+/// The hyper clients of requests isolated with
+/// `RequestBuilder::socks_isolation`, so that each credential has a pool of
+/// its own.
+#[cfg(feature = "socks")]
+struct IsolatedClients {
+    builder: hyper::client::Builder,
+    connector: Connector,
+    /// The clients of the most recently used credentials, last.
+    clients: Mutex<Vec<(SocksIsolation, HyperClient)>>,
+}
+
+#[cfg(feature = "socks")]
+impl IsolatedClients {
+    fn get(&self, isolation: &SocksIsolation) -> HyperClient {
+        let mut clients = self.clients.lock().unwrap();
+        let entry = match clients.iter().position(|(used, _)| used == isolation) {
+            Some(i) => clients.remove(i),
+            None => {
+                if clients.len() == MAX_ISOLATED_CLIENTS {
+                    // Requests still using the evicted client keep it.
+                    clients.remove(0);
+                }
+                let connector = self.connector.isolated(isolation.clone());
+                (isolation.clone(), self.builder.build(connector))
+            }
+        };
+        let client = entry.1.clone();
+        clients.push(entry);
+        client
+    }
 }
 
 impl ClientRef {
+    /// The `Authorization` header from the `.netrc` credentials for `url`,
+    /// unless `headers` already have one.
+    fn netrc_authorization(&self, headers: &HeaderMap, url: &Url) -> Option<HeaderValue> {
+        if headers.contains_key(AUTHORIZATION) {
+            return None;
+        }
+        self.netrc.as_ref()?.authorization(url.host_str()?)
+    }
+
+    /// Removes the sensitive headers of a redirect to `next`, which HSTS may
+    /// have upgraded, comparing it with the previous URL upgraded the same
+    /// way, so a same-host redirect doesn't look like it changed origin.
+    fn remove_sensitive_headers(&self, headers: &mut HeaderMap, next: &Url, previous: &[Url]) {
+        if let (Some(hsts), Some(last)) = (self.hsts.as_ref(), previous.last()) {
+            let mut upgraded = last.clone();
+            if hsts.upgrade(&mut upgraded) {
+                self.redirect_policy.remove_sensitive_headers(
+                    headers,
+                    next,
+                    std::slice::from_ref(&upgraded),
+                );
+                return;
+            }
+        }
+        self.redirect_policy
+            .remove_sensitive_headers(headers, next, previous);
+    }
+
+    /// The hyper client to send a request with `method` and `extensions`
+    /// with.
+    #[cfg_attr(
+        not(all(feature = "rustls-tls", feature = "socks")),
+        allow(unused_variables)
+    )]
+    fn hyper_for(&self, method: &Method, extensions: &Extensions) -> HyperClient {
+        #[cfg(feature = "socks")]
+        {
+            if let Some(isolation) = extensions.get::<SocksIsolation>() {
+                return self.isolated.get(isolation);
+            }
+        }
+        // Early data can be replayed by an attacker, so it is only used for
+        // requests without side effects.
+        #[cfg(feature = "rustls-tls")]
+        {
+            if let Some(ref hyper) = self.hyper_early_data {
+                if method.is_safe() {
+                    return hyper.clone();
+                }
+            }
+        }
+        self.hyper.clone()
+    }
+
     fn fmt_fields(&self, f: &mut fmt::DebugStruct<'_, '_>) {
         // Instead of deriving Debug, only print fields when their output
         // would provide relevant or interesting data.
@@ -1227,6 +2129,10 @@ impl ClientRef {
             }
         }
 
+        if self.hsts.is_some() {
+            f.field("hsts", &true);
+        }
+
         f.field("accepts", &self.accepts);
 
         if !self.proxies.is_empty() {
@@ -1241,6 +2147,18 @@ impl ClientRef {
             f.field("referer", &true);
         }
 
+        if self.auth.is_some() {
+            f.field("auth", &true);
+        }
+
+        if self.signer.is_some() {
+            f.field("signer", &true);
+        }
+
+        if self.netrc.is_some() {
+            f.field("netrc", &true);
+        }
+
         f.field("default_headers", &self.headers);
 
         if let Some(ref d) = self.request_timeout {
@@ -1254,22 +2172,45 @@ pub(super) struct Pending {
 }
 
 enum PendingInner {
+    Authorizing(AuthorizingRequest),
     Request(PendingRequest),
     Error(Option<crate::Error>),
 }
 
+/// A request waiting on the `Authorization` header of an `AuthProvider`.
+struct AuthorizingRequest {
+    client: Client,
+    req: Option<Request>,
+    authorization: AuthFuture,
+}
+
 struct PendingRequest {
     method: Method,
     url: Url,
     headers: HeaderMap,
     body: Option<Option<Bytes>>,
+    extensions: Extensions,
 
     urls: Vec<Url>,
+    history: Vec<redirect::Hop>,
 
     client: Arc<ClientRef>,
 
     in_flight: ResponseFuture,
     timeout: Option<Delay>,
+    pending_redirect: Option<PendingRedirect>,
+    pending_auth: Option<PendingAuth>,
+}
+
+/// A request sent again on a connection being authenticated.
+type PendingAuth =
+    Pin<Box<dyn Future<Output = Result<hyper::Response<hyper::Body>, BoxError>> + Send>>;
+
+/// A redirect waiting on an asynchronous `redirect::Policy` to decide.
+struct PendingRedirect {
+    action: Pin<Box<dyn Future<Output = redirect::ActionKind> + Send>>,
+    res: hyper::Response<hyper::Body>,
+    loc: Url,
 }
 
 impl PendingRequest {
@@ -1285,9 +2226,217 @@ impl PendingRequest {
         unsafe { &mut Pin::get_unchecked_mut(self).urls }
     }
 
+    fn history(self: Pin<&mut Self>) -> &mut Vec<redirect::Hop> {
+        unsafe { &mut Pin::get_unchecked_mut(self).history }
+    }
+
     fn headers(self: Pin<&mut Self>) -> &mut HeaderMap {
         unsafe { &mut Pin::get_unchecked_mut(self).headers }
     }
+
+    fn extensions(self: Pin<&mut Self>) -> &mut Extensions {
+        unsafe { &mut Pin::get_unchecked_mut(self).extensions }
+    }
+
+    fn pending_redirect(self: Pin<&mut Self>) -> &mut Option<PendingRedirect> {
+        unsafe { &mut Pin::get_unchecked_mut(self).pending_redirect }
+    }
+
+    fn pending_auth(self: Pin<&mut Self>) -> &mut Option<PendingAuth> {
+        unsafe { &mut Pin::get_unchecked_mut(self).pending_auth }
+    }
+
+    /// Starts authenticating with a server that answered with `res`, a
+    /// `401 Unauthorized`, if the client's `AuthProvider` supports one of its
+    /// challenges.
+    fn authenticate(
+        &self,
+        res: hyper::Response<hyper::Body>,
+    ) -> Result<PendingAuth, Box<hyper::Response<hyper::Body>>> {
+        let provider = match self.client.auth {
+            Some(ref provider) => provider,
+            None => return Err(Box::new(res)),
+        };
+        let body = match self.body {
+            Some(Some(ref body)) => Some(body.clone()),
+            Some(None) => return Err(Box::new(res)),
+            None => None,
+        };
+
+        let challenges = Challenges::parse(res.headers(), WWW_AUTHENTICATE);
+        let exchange = self
+            .url
+            .host_str()
+            .and_then(|host| Exchange::start(&**provider, host, &challenges));
+        if let Some(exchange) = exchange {
+            return Ok(Box::pin(authenticate(
+                self.client.hyper_for(&self.method, &self.extensions),
+                exchange,
+                self.method.clone(),
+                expect_uri(&self.url),
+                self.headers.clone(),
+                body,
+            )));
+        }
+
+        // Otherwise the provider may refresh the `Authorization` header, but
+        // not for another origin a redirect led to, which the header of the
+        // first request may not be meant for.
+        let first = self.urls.first().unwrap_or(&self.url);
+        if first.origin() != self.url.origin() {
+            return Err(Box::new(res));
+        }
+        let unauthorized = provider.unauthorized(&self.url, res.headers());
+        let hyper = self.client.hyper_for(&self.method, &self.extensions);
+        let mut req = hyper::Request::builder()
+            .method(self.method.clone())
+            .uri(expect_uri(&self.url))
+            .body(())
+            .expect("valid request parts");
+        *req.headers_mut() = self.headers.clone();
+        Ok(Box::pin(async move {
+            let authorization = match unauthorized.await? {
+                Some(authorization) => authorization,
+                None => return Ok(res),
+            };
+            debug!("sending request again with a refreshed authorization");
+            let body = match body {
+                Some(body) => Body::reusable(body),
+                None => Body::empty(),
+            };
+            let (mut parts, ()) = req.into_parts();
+            parts.headers.insert(AUTHORIZATION, authorization);
+            let req = hyper::Request::from_parts(parts, body.into_stream());
+            Ok(hyper.request(req).await?)
+        }))
+    }
+
+    /// Sends the next request of a redirect chain.
+    ///
+    /// `headers` are used as-is when given, otherwise the previous request's
+    /// headers are reused, with sensitive headers removed.
+    fn follow_redirect(
+        mut self: Pin<&mut Self>,
+        res: &hyper::Response<hyper::Body>,
+        loc: Url,
+        headers: Option<HeaderMap>,
+    ) -> crate::Result<()> {
+        debug!("redirecting '{}' to '{}'", self.url, loc);
+        let hop = redirect::Hop::new(self.url.clone(), res.status(), res.headers().clone());
+        self.as_mut().history().push(hop);
+        self.url = loc;
+
+        let mut headers = match headers {
+            Some(headers) => headers,
+            None => {
+                let mut headers = std::mem::replace(self.as_mut().headers(), HeaderMap::new());
+                self.client
+                    .remove_sensitive_headers(&mut headers, &self.url, &self.urls);
+                headers
+            }
+        };
+        if let Some(authorization) = self.client.netrc_authorization(&headers, &self.url) {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        let mut body = match self.body {
+            Some(Some(ref body)) => Body::reusable(body.clone()),
+            _ => Body::empty(),
+        };
+
+        // Add cookies from the cookie store.
+        #[cfg(feature = "cookies")]
+        {
+            if let Some(cookie_store_wrapper) = self.client.cookie_store.as_ref() {
+                let cookie_store = cookie_store_wrapper.read().unwrap();
+                add_cookie_header(&mut headers, &cookie_store, &self.url);
+            }
+        }
+
+        if let Some(signer) = self.client.signer.clone() {
+            let mut req = Request::new(self.method.clone(), self.url.clone());
+            *req.headers_mut() = headers;
+            if let Some(Some(_)) = self.body {
+                *req.body_mut() = Some(body);
+            }
+            if let Err(err) = sign::sign(&*signer, &mut req) {
+                return Err(error::request(err).with_url(self.url.clone()));
+            }
+            let (method, url, signed_headers, signed_body, _, _) = req.pieces();
+            self.method = method;
+            self.url = url;
+            headers = signed_headers;
+            body = signed_body.unwrap_or_else(Body::empty);
+        }
+
+        let mut req = hyper::Request::builder()
+            .method(self.method.clone())
+            .uri(expect_uri(&self.url))
+            .body(body.into_stream())
+            .expect("valid request parts");
+        *req.headers_mut() = headers.clone();
+        std::mem::swap(self.as_mut().headers(), &mut headers);
+        *self.as_mut().in_flight().get_mut() = self.client
+            .hyper_for(&self.method, &self.extensions)
+            .request(req);
+        Ok(())
+    }
+
+    fn into_response(mut self: Pin<&mut Self>, res: hyper::Response<hyper::Body>) -> Response {
+        debug!("response '{}' for {}", res.status(), self.url);
+        let extensions = std::mem::replace(self.as_mut().extensions(), Extensions::new());
+        let history = std::mem::take(self.as_mut().history());
+        Response::new(
+            res,
+            self.url.clone(),
+            extensions,
+            history,
+            self.client.accepts,
+            self.timeout.take(),
+        )
+    }
+}
+
+/// Sends a request with the client's pool, answering the `401 Unauthorized`
+/// challenges to it with `exchange` until the server accepts a token or
+/// rejects the exchange.
+async fn authenticate(
+    hyper: HyperClient,
+    mut exchange: Exchange,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Option<Bytes>,
+) -> Result<hyper::Response<hyper::Body>, BoxError> {
+    let mut authorization = exchange.respond(None)?;
+    loop {
+        let body = match body {
+            Some(ref body) => Body::from(body.clone()),
+            None => Body::empty(),
+        };
+        let mut req = hyper::Request::builder()
+            .method(method.clone())
+            .uri(uri.clone())
+            .body(body.into_stream())
+            .expect("valid request parts");
+        *req.headers_mut() = headers.clone();
+        if let Some(value) = authorization {
+            req.headers_mut().insert(AUTHORIZATION, value);
+        }
+
+        let res = hyper.request(req).await?;
+        if res.status() != StatusCode::UNAUTHORIZED {
+            return Ok(res);
+        }
+        let challenges = Challenges::parse(res.headers(), WWW_AUTHENTICATE);
+        authorization = exchange.respond(Some(&challenges))?;
+        if authorization.is_none() {
+            return Ok(res);
+        }
+        // The next token goes on this connection, which is back in the pool
+        // once the response is read, and the pool hands out the connection
+        // that was idle last first.
+        hyper::body::to_bytes(res.into_body()).await?;
+    }
 }
 
 impl Pending {
@@ -1305,14 +2454,36 @@ impl Pending {
 impl Future for Pending {
     type Output = Result<Response, crate::Error>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let inner = self.inner();
-        match inner.get_mut() {
-            PendingInner::Request(ref mut req) => Pin::new(req).poll(cx),
-            PendingInner::Error(ref mut err) => Poll::Ready(Err(err
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.as_mut().inner();
+        let sent = match inner.get_mut() {
+            PendingInner::Authorizing(ref mut authorizing) => {
+                let authorization = match authorizing.authorization.as_mut().poll(cx) {
+                    Poll::Ready(authorization) => authorization,
+                    Poll::Pending => return Poll::Pending,
+                };
+                let mut req = authorizing
+                    .req
+                    .take()
+                    .expect("Pending authorization polled more than once");
+                match authorization {
+                    Ok(Some(authorization)) => {
+                        req.headers_mut().insert(AUTHORIZATION, authorization);
+                    }
+                    Ok(None) => (),
+                    Err(e) => {
+                        return Poll::Ready(Err(crate::error::request(e).with_url(req.url().clone())));
+                    }
+                }
+                authorizing.client.send_request(req)
+            }
+            PendingInner::Request(ref mut req) => return Pin::new(req).poll(cx),
+            PendingInner::Error(ref mut err) => return Poll::Ready(Err(err
                 .take()
                 .expect("Pending error polled more than once"))),
-        }
+        };
+        *self.as_mut().inner().get_mut() = sent.inner;
+        self.poll(cx)
     }
 }
 
@@ -1329,147 +2500,187 @@ impl Future for PendingRequest {
         }
 
         loop {
-            let res = match self.as_mut().in_flight().as_mut().poll(cx) {
-                Poll::Ready(Err(e)) => {
-                    return Poll::Ready(Err(crate::error::request(e).with_url(self.url.clone())));
-                }
-                Poll::Ready(Ok(res)) => res,
-                Poll::Pending => return Poll::Pending,
+            // An asynchronous redirect policy may still be deciding.
+            let decided = match self.as_mut().pending_redirect() {
+                Some(pending) => match pending.action.as_mut().poll(cx) {
+                    Poll::Ready(action) => Some(action),
+                    Poll::Pending => return Poll::Pending,
+                },
+                None => None,
             };
 
-            #[cfg(feature = "cookies")]
-            {
-                if let Some(store_wrapper) = self.client.cookie_store.as_ref() {
-                    let mut cookies = cookie::extract_response_cookies(&res.headers())
-                        .filter_map(|res| res.ok())
-                        .map(|cookie| cookie.into_inner().into_owned())
-                        .peekable();
-                    if cookies.peek().is_some() {
-                      let mut store = store_wrapper.write().unwrap();
-                      store.0.store_response_cookies(cookies, &self.url);
+            let (res, loc, action) = if let Some(action) = decided {
+                let pending = self
+                    .as_mut()
+                    .pending_redirect()
+                    .take()
+                    .expect("pending redirect was just polled");
+                (pending.res, pending.loc, action)
+            } else {
+                let authenticating = self.pending_auth.is_some();
+                let res = match self.as_mut().pending_auth() {
+                    Some(pending) => pending.as_mut().poll(cx),
+                    None => self.as_mut().in_flight().as_mut().poll(cx).map_err(BoxError::from),
+                };
+                let res = match res {
+                    Poll::Ready(Err(e)) => {
+                        return Poll::Ready(Err(crate::error::request(e).with_url(self.url.clone())));
                     }
+                    Poll::Ready(Ok(res)) => res,
+                    Poll::Pending => return Poll::Pending,
+                };
+                if authenticating {
+                    *self.as_mut().pending_auth() = None;
                 }
-            }
-            let should_redirect = match res.status() {
-                StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
-                    self.body = None;
-                    for header in &[
-                        TRANSFER_ENCODING,
-                        CONTENT_ENCODING,
-                        CONTENT_TYPE,
-                        CONTENT_LENGTH,
-                    ] {
-                        self.headers.remove(header);
-                    }
 
-                    match self.method {
-                        Method::GET | Method::HEAD => {}
-                        _ => {
-                            self.method = Method::GET;
+                #[cfg(feature = "cookies")]
+                {
+                    if let Some(store_wrapper) = self.client.cookie_store.as_ref() {
+                        let mut cookies = cookie::extract_response_cookies(&res.headers())
+                            .filter_map(|res| res.ok())
+                            .map(|cookie| cookie.into_inner().into_owned())
+                            .peekable();
+                        if cookies.peek().is_some() {
+                          let mut store = store_wrapper.write().unwrap();
+                          store.0.store_response_cookies(cookies, &self.url);
                         }
                     }
-                    true
                 }
-                StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {
-                    match self.body {
-                        Some(Some(_)) | None => true,
-                        Some(None) => false,
-                    }
+                if let Some(hsts) = self.client.hsts.as_ref() {
+                    hsts.store_response(&self.url, res.headers());
                 }
-                _ => false,
-            };
-            if should_redirect {
-                let loc = res.headers().get(LOCATION).and_then(|val| {
-                    let loc = (|| -> Option<Url> {
-                        // Some sites may send a utf-8 Location header,
-                        // even though we're supposed to treat those bytes
-                        // as opaque, we'll check specifically for utf8.
-                        self.url.join(str::from_utf8(val.as_bytes()).ok()?).ok()
-                    })();
-
-                    // Check that the `url` is also a valid `http::Uri`.
-                    //
-                    // If not, just log it and skip the redirect.
-                    let loc = loc.and_then(|url| {
-                        if try_uri(&url).is_some() {
-                            Some(url)
-                        } else {
-                            None
+
+                let res = if res.status() == StatusCode::UNAUTHORIZED && !authenticating {
+                    match self.authenticate(res) {
+                        Ok(pending) => {
+                            *self.as_mut().pending_auth() = Some(pending);
+                            continue;
+                        }
+                        Err(res) => *res,
+                    }
+                } else {
+                    res
+                };
+
+                let should_redirect = match res.status() {
+                    StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+                        self.body = None;
+                        for header in &[
+                            TRANSFER_ENCODING,
+                            CONTENT_ENCODING,
+                            CONTENT_TYPE,
+                            CONTENT_LENGTH,
+                        ] {
+                            self.headers.remove(header);
                         }
-                    });
 
-                    if loc.is_none() {
-                        debug!("Location header had invalid URI: {:?}", val);
+                        match self.method {
+                            Method::GET | Method::HEAD => {}
+                            _ => {
+                                self.method = Method::GET;
+                            }
+                        }
+                        true
                     }
-                    loc
-                });
-                if let Some(loc) = loc {
-                    if self.client.referer {
-                        if let Some(referer) = make_referer(&loc, &self.url) {
-                            self.headers.insert(REFERER, referer);
+                    StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {
+                        match self.body {
+                            Some(Some(_)) | None => true,
+                            Some(None) => false,
                         }
                     }
-                    let url = self.url.clone();
-                    self.as_mut().urls().push(url);
-                    let action = self
-                        .client
-                        .redirect_policy
-                        .check(res.status(), &loc, &self.urls);
-
-                    match action {
-                        redirect::ActionKind::Follow => {
-                            debug!("redirecting '{}' to '{}'", self.url, loc);
-                            self.url = loc;
-
-                            let mut headers =
-                                std::mem::replace(self.as_mut().headers(), HeaderMap::new());
-
-                            remove_sensitive_headers(&mut headers, &self.url, &self.urls);
-                            let uri = expect_uri(&self.url);
-                            let body = match self.body {
-                                Some(Some(ref body)) => Body::reusable(body.clone()),
-                                _ => Body::empty(),
-                            };
-                            let mut req = hyper::Request::builder()
-                                .method(self.method.clone())
-                                .uri(uri.clone())
-                                .body(body.into_stream())
-                                .expect("valid request parts");
-
-                            // Add cookies from the cookie store.
-                            #[cfg(feature = "cookies")]
-                            {
-                                if let Some(cookie_store_wrapper) =
-                                    self.client.cookie_store.as_ref()
-                                {
-                                    let cookie_store = cookie_store_wrapper.read().unwrap();
-                                    add_cookie_header(&mut headers, &cookie_store, &self.url);
-                                }
+                    _ => false,
+                };
+                let loc = if should_redirect {
+                    res.headers().get(LOCATION).and_then(|val| {
+                        let loc = (|| -> Option<Url> {
+                            // Some sites may send a utf-8 Location header,
+                            // even though we're supposed to treat those bytes
+                            // as opaque, we'll check specifically for utf8.
+                            self.url.join(str::from_utf8(val.as_bytes()).ok()?).ok()
+                        })();
+
+                        // Check that the `url` is also a valid `http::Uri`.
+                        //
+                        // If not, just log it and skip the redirect.
+                        let loc = loc.and_then(|url| {
+                            if try_uri(&url).is_some() {
+                                Some(url)
+                            } else {
+                                None
                             }
+                        });
 
-                            *req.headers_mut() = headers.clone();
-                            std::mem::swap(self.as_mut().headers(), &mut headers);
-                            *self.as_mut().in_flight().get_mut() = self.client.hyper.request(req);
-                            continue;
-                        }
-                        redirect::ActionKind::Stop => {
-                            debug!("redirect policy disallowed redirection to '{}'", loc);
-                        }
-                        redirect::ActionKind::Error(err) => {
-                            return Poll::Ready(Err(crate::error::redirect(err, self.url.clone())));
+                        if loc.is_none() {
+                            debug!("Location header had invalid URI: {:?}", val);
                         }
+                        loc
+                    })
+                } else {
+                    None
+                };
+                let mut loc = match loc {
+                    Some(loc) => loc,
+                    None => return Poll::Ready(Ok(self.into_response(res))),
+                };
+
+                if let Some(hsts) = self.client.hsts.as_ref() {
+                    if hsts.upgrade(&mut loc) {
+                        debug!("HSTS upgraded redirect to '{}'", loc);
+                    }
+                }
+
+                if self.client.referer {
+                    if let Some(referer) = make_referer(&loc, &self.url) {
+                        self.headers.insert(REFERER, referer);
+                    }
+                }
+                let url = self.url.clone();
+                self.as_mut().urls().push(url);
+
+                if self.client.redirect_policy.is_async() {
+                    let mut headers = self.headers.clone();
+                    self.client
+                        .remove_sensitive_headers(&mut headers, &loc, &self.urls);
+                    let action = self.client.redirect_policy.check_async(
+                        res.status(),
+                        res.headers().clone(),
+                        loc.clone(),
+                        self.urls.clone(),
+                        headers,
+                    );
+                    *self.as_mut().pending_redirect() = Some(PendingRedirect { action, res, loc });
+                    continue;
+                }
+
+                let action = self
+                    .client
+                    .redirect_policy
+                    .check(res.status(), res.headers(), &loc, &self.urls);
+                (res, loc, action)
+            };
+
+            match action {
+                redirect::ActionKind::Follow => {
+                    if let Err(err) = self.as_mut().follow_redirect(&res, loc, None) {
+                        return Poll::Ready(Err(err));
                     }
+                    continue;
+                }
+                redirect::ActionKind::FollowWith(headers) => {
+                    if let Err(err) = self.as_mut().follow_redirect(&res, loc, Some(headers)) {
+                        return Poll::Ready(Err(err));
+                    }
+                    continue;
+                }
+                redirect::ActionKind::Stop => {
+                    debug!("redirect policy disallowed redirection to '{}'", loc);
+                }
+                redirect::ActionKind::Error(err) => {
+                    return Poll::Ready(Err(crate::error::redirect(err, self.url.clone())));
                 }
             }
 
-            debug!("response '{}' for {}", res.status(), self.url);
-            let res = Response::new(
-                res,
-                self.url.clone(),
-                self.client.accepts,
-                self.timeout.take(),
-            );
-            return Poll::Ready(Ok(res));
+            return Poll::Ready(Ok(self.into_response(res)));
         }
     }
 }
@@ -1477,6 +2688,13 @@ impl Future for PendingRequest {
 impl fmt::Debug for Pending {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.inner {
+            PendingInner::Authorizing(ref authorizing) => {
+                let req = authorizing.req.as_ref();
+                f.debug_struct("Pending")
+                    .field("method", &req.map(|req| req.method()))
+                    .field("url", &req.map(|req| req.url()))
+                    .finish()
+            }
             PendingInner::Request(ref req) => f
                 .debug_struct("Pending")
                 .field("method", &req.method)
@@ -1517,6 +2735,29 @@ fn add_cookie_header(headers: &mut HeaderMap, cookie_store: &cookie::CookieStore
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "socks")]
+    #[test]
+    fn isolated_clients_are_bounded() {
+        use super::{SocksIsolation, MAX_ISOLATED_CLIENTS};
+
+        let client = super::Client::new();
+        let isolated = &client.inner.isolated;
+        let isolation = |i: usize| SocksIsolation::new(i.to_string(), String::new());
+        for i in 0..=MAX_ISOLATED_CLIENTS {
+            isolated.get(&isolation(i));
+        }
+        // Using the oldest credentials again keeps them.
+        isolated.get(&isolation(1));
+        isolated.get(&isolation(MAX_ISOLATED_CLIENTS + 1));
+
+        let clients = isolated.clients.lock().unwrap();
+        assert_eq!(clients.len(), MAX_ISOLATED_CLIENTS);
+        let used = clients.iter().map(|(used, _)| used.clone()).collect::<Vec<_>>();
+        assert!(!used.contains(&isolation(0)));
+        assert!(!used.contains(&isolation(2)));
+        assert_eq!(used[used.len() - 2], isolation(1));
+    }
+
     #[tokio::test]
     async fn execute_request_rejects_invald_urls() {
         let url_str = "hxxps://www.rust-lang.org/";
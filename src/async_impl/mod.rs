@@ -1,7 +1,7 @@
 pub use self::body::Body;
 pub use self::client::{Client, ClientBuilder};
 pub use self::request::{Request, RequestBuilder};
-pub use self::response::{Response, ResponseBuilderExt};
+pub use self::response::Response;
 
 #[cfg(feature = "blocking")]
 pub(crate) use self::decoder::Decoder;
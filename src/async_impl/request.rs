@@ -14,8 +14,10 @@ use super::client::{Client, Pending};
 use super::multipart;
 use super::response::Response;
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+#[cfg(feature = "socks")]
+use crate::proxy::SocksIsolation;
 use crate::{Method, Url};
-use http::{Request as HttpRequest, request::Parts};
+use http::{Extensions, Request as HttpRequest, request::Parts};
 
 /// A request which can be executed with `Client::execute()`.
 pub struct Request {
@@ -24,6 +26,7 @@ pub struct Request {
     headers: HeaderMap,
     body: Option<Body>,
     timeout: Option<Duration>,
+    extensions: Extensions,
 }
 
 /// A builder to construct the properties of a `Request`.
@@ -42,7 +45,8 @@ impl Request {
             url,
             headers: HeaderMap::new(),
             body: None,
-            timeout: None
+            timeout: None,
+            extensions: Extensions::new(),
         }
     }
 
@@ -106,9 +110,23 @@ impl Request {
         &mut self.timeout
     }
 
+    /// Get the extensions.
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get a mutable reference to the extensions.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Attempt to clone the request.
     ///
     /// `None` is returned if the request can not be cloned, i.e. if the body is a stream.
+    ///
+    /// Extensions are not cloned, since `Extensions` itself isn't `Clone`.
     pub fn try_clone(&self) -> Option<Request> {
         let body = match self.body.as_ref() {
             Some(ref body) => Some(body.try_clone()?),
@@ -121,8 +139,24 @@ impl Request {
         Some(req)
     }
 
-    pub(super) fn pieces(self) -> (Method, Url, HeaderMap, Option<Body>, Option<Duration>) {
-        (self.method, self.url, self.headers, self.body, self.timeout)
+    pub(super) fn pieces(
+        self,
+    ) -> (
+        Method,
+        Url,
+        HeaderMap,
+        Option<Body>,
+        Option<Duration>,
+        Extensions,
+    ) {
+        (
+            self.method,
+            self.url,
+            self.headers,
+            self.body,
+            self.timeout,
+            self.extensions,
+        )
     }
 }
 
@@ -239,6 +273,30 @@ impl RequestBuilder {
         self
     }
 
+    /// Isolates this request from others through SOCKS proxies, with the
+    /// given credentials.
+    ///
+    /// Requests with different credentials don't share connections, and
+    /// SOCKS proxies get these credentials instead of their own. Tor, for
+    /// example, uses separate circuits for them. They are only sent to SOCKS
+    /// proxies, never to a server or an HTTP proxy.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `socks` feature to be enabled.
+    #[cfg(feature = "socks")]
+    pub fn socks_isolation<U, P>(mut self, username: U, password: P) -> RequestBuilder
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        if let Ok(ref mut req) = self.request {
+            let isolation = SocksIsolation::new(username.into(), password.into());
+            req.extensions_mut().insert(isolation);
+        }
+        self
+    }
+
     /// Sends a multipart/form-data body.
     ///
     /// ```
@@ -508,6 +566,7 @@ impl<T> TryFrom<HttpRequest<T>> for Request where T:Into<Body>{
             method,
             uri,
             headers,
+            extensions,
             ..
         } = parts;
         let url = Url::parse(&uri.to_string())
@@ -518,10 +577,36 @@ impl<T> TryFrom<HttpRequest<T>> for Request where T:Into<Body>{
             headers,
             body: Some(body.into()),
             timeout: None,
+            extensions,
         })
     }
 }
 
+impl TryFrom<Request> for HttpRequest<Body> {
+    type Error = crate::Error;
+
+    fn try_from(req: Request) -> crate::Result<Self> {
+        let Request {
+            method,
+            url,
+            headers,
+            body,
+            extensions,
+            ..
+        } = req;
+
+        let mut req = HttpRequest::builder()
+            .method(method)
+            .uri(url.as_str())
+            .body(body.unwrap_or_else(Body::empty))
+            .map_err(crate::error::builder)?;
+
+        *req.headers_mut() = headers;
+        *req.extensions_mut() = extensions;
+        Ok(req)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Client, HttpRequest, Request};
@@ -746,6 +831,29 @@ mod tests {
         assert_eq!(req.url().as_str(), "http://localhost/");
     }
 
+    #[test]
+    fn convert_into_http_request() {
+        let client = Client::new();
+        let mut req = client
+            .post("http://localhost/path?q=1")
+            .header("User-Agent", "my-awesome-agent/1.0")
+            .body("test test test")
+            .build()
+            .expect("request build");
+        req.extensions_mut().insert(42u32);
+
+        let http_request = HttpRequest::<super::Body>::try_from(req).unwrap();
+        assert_eq!(http_request.method(), Method::POST);
+        assert_eq!(http_request.uri(), "http://localhost/path?q=1");
+        assert_eq!(
+            http_request.headers()["User-Agent"],
+            "my-awesome-agent/1.0"
+        );
+        assert_eq!(http_request.extensions().get::<u32>(), Some(&42));
+        let test_data = b"test test test";
+        assert_eq!(http_request.body().as_bytes(), Some(&test_data[..]));
+    }
+
     /*
     use {body, Method};
     use super::Client;
@@ -19,6 +19,10 @@ use super::body::Body;
 use super::decoder::{Accepts, Decoder};
 #[cfg(feature = "cookies")]
 use crate::cookie;
+use crate::redirect;
+use crate::response::ResponseUrl;
+#[cfg(feature = "__tls")]
+use crate::tls::TlsInfo;
 
 /// A Response to a submitted `Request`.
 pub struct Response {
@@ -30,19 +34,34 @@ pub struct Response {
     body: Decoder,
     version: Version,
     extensions: http::Extensions,
+    history: Vec<redirect::Hop>,
 }
 
 impl Response {
     pub(super) fn new(
         res: hyper::Response<hyper::Body>,
         url: Url,
+        mut extensions: http::Extensions,
+        history: Vec<redirect::Hop>,
         accepts: Accepts,
         timeout: Option<Delay>,
     ) -> Response {
         let (parts, body) = res.into_parts();
         let status = parts.status;
         let version = parts.version;
-        let extensions = parts.extensions;
+        // Extensions set by the connection (such as `HttpInfo`) take
+        // precedence over those carried along from the `Request`.
+        extensions.extend(parts.extensions);
+        // Connections that sent early data only know their `TlsInfo` after
+        // the handshake, which is done once a response is read.
+        #[cfg(feature = "rustls-tls")]
+        {
+            if let Some(pending) = extensions.remove::<crate::tls::PendingTlsInfo>() {
+                if let Some(info) = pending.get() {
+                    extensions.insert(info);
+                }
+            }
+        }
 
         let mut headers = parts.headers;
         let decoder = Decoder::detect(&mut headers, Body::response(body, timeout), accepts);
@@ -54,6 +73,7 @@ impl Response {
             body: decoder,
             version,
             extensions,
+            history,
         }
     }
 
@@ -112,6 +132,15 @@ impl Response {
         &self.url
     }
 
+    /// Get the redirects that were followed to get this `Response`.
+    ///
+    /// Each `Hop` holds the URL that was requested along with the status and
+    /// headers of the redirect response it returned, in the order they were
+    /// followed. The list is empty if no redirect was followed.
+    pub fn redirect_history(&self) -> &[redirect::Hop] {
+        &self.history
+    }
+
     /// Get the remote address used to get this `Response`.
     pub fn remote_addr(&self) -> Option<SocketAddr> {
         self.extensions
@@ -119,6 +148,35 @@ impl Response {
             .map(|info| info.remote_addr())
     }
 
+    /// Get information about the TLS connection this `Response` was
+    /// received on.
+    ///
+    /// Returns `None` if the connection did not use TLS.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.extensions.get::<TlsInfo>()
+    }
+
+    /// Get the extensions of this `Response`.
+    ///
+    /// This includes any extensions that were set on the `Request` that
+    /// produced it.
+    #[inline]
+    pub fn extensions(&self) -> &http::Extensions {
+        &self.extensions
+    }
+
+    /// Get a mutable reference to the extensions of this `Response`.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut http::Extensions {
+        &mut self.extensions
+    }
+
     // body methods
 
     /// Get the full response text.
@@ -411,6 +469,7 @@ impl<T: Into<Body>> From<http::Response<T>> for Response {
             body,
             version: parts.version,
             extensions: parts.extensions,
+            history: Vec::new(),
         }
     }
 }
@@ -422,45 +481,13 @@ impl From<Response> for Body {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct ResponseUrl(Url);
-
-/// Extension trait for http::response::Builder objects
-///
-/// Allows the user to add a `Url` to the http::Response
-pub trait ResponseBuilderExt {
-    /// A builder method for the `http::response::Builder` type that allows the user to add a `Url`
-    /// to the `http::Response`
-    fn url(self, url: Url) -> Self;
-}
-
-impl ResponseBuilderExt for http::response::Builder {
-    fn url(self, url: Url) -> Self {
-        self.extension(ResponseUrl(url))
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{Response, ResponseBuilderExt, ResponseUrl};
+    use super::Response;
+    use crate::ResponseBuilderExt;
     use http::response::Builder;
     use url::Url;
 
-    #[test]
-    fn test_response_builder_ext() {
-        let url = Url::parse("http://example.com").unwrap();
-        let response = Builder::new()
-            .status(200)
-            .url(url.clone())
-            .body(())
-            .unwrap();
-
-        assert_eq!(
-            response.extensions().get::<ResponseUrl>(),
-            Some(&ResponseUrl(url))
-        );
-    }
-
     #[test]
     fn test_from_http_response() {
         let url = Url::parse("http://example.com").unwrap();
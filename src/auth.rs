@@ -0,0 +1,292 @@
+//! Authentication with servers and proxies.
+//!
+//! An [`AuthProvider`] given to [`ClientBuilder::auth`] authenticates the
+//! requests of a client in one of two ways:
+//!
+//! - Per request, with an `Authorization` header, like a bearer token. When
+//!   a server answers `401 Unauthorized`, the provider may refresh the header,
+//!   and the request is sent once more.
+//! - Per connection, for servers that answer a request with a challenge, and
+//!   expect tokens on the same connection until they accepted one. Such
+//!   exchanges can also answer the `Proxy-Authenticate` challenges to the
+//!   `CONNECT` requests tunneling to HTTPS servers, with [`Proxy::auth`].
+//!
+//! Credentials of a `.netrc` file, given to [`ClientBuilder::netrc`], are
+//! sent with Basic authentication to the hosts and proxies they are for.
+//!
+//! With the `json` feature, [`OAuth2`] gets bearer tokens from an OAuth 2.0
+//! token endpoint. With the `negotiate` feature, [`Ntlm`] answers `NTLM` and
+//! `Negotiate` challenges with NTLMv2. Kerberos needs the platform's GSSAPI or
+//! SSPI, so it is left to other `AuthProvider`s.
+//!
+//! [`ClientBuilder::auth`]: ../struct.ClientBuilder.html#method.auth
+//! [`ClientBuilder::netrc`]: ../struct.ClientBuilder.html#method.netrc
+//! [`Proxy::auth`]: ../struct.Proxy.html#method.auth
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::Url;
+
+mod netrc;
+#[cfg(feature = "negotiate")]
+mod ntlm;
+#[cfg(feature = "json")]
+mod oauth2;
+
+pub use self::netrc::IntoNetrc;
+pub(crate) use self::netrc::Netrc;
+#[cfg(feature = "negotiate")]
+pub use self::ntlm::Ntlm;
+#[cfg(feature = "json")]
+pub use self::oauth2::OAuth2;
+
+/// The most tokens sent in one exchange, to stop servers that never accept.
+const MAX_LEGS: usize = 4;
+
+/// A future resolving to an `Authorization` header, if there is one.
+pub type AuthFuture = Pin<
+    Box<dyn Future<Output = Result<Option<HeaderValue>, Box<dyn StdError + Send + Sync>>> + Send>,
+>;
+
+/// Authenticates requests, or connections to servers offering some schemes.
+///
+/// All methods have defaults doing nothing, so providers only implement the
+/// way they authenticate.
+pub trait AuthProvider: Send + Sync {
+    /// Returns the `Authorization` header for a request to `url`.
+    ///
+    /// This isn't called for requests that already have an `Authorization`
+    /// header.
+    fn authorization(&self, url: &Url) -> AuthFuture {
+        let _ = url;
+        Box::pin(futures_util::future::ready(Ok(None)))
+    }
+
+    /// Called when a server answered a request to `url` with
+    /// `401 Unauthorized` and `headers`, returning the `Authorization` header
+    /// to send the request once more with.
+    ///
+    /// Returns `None` to give the response to the caller instead.
+    fn unauthorized(&self, url: &Url, headers: &HeaderMap) -> AuthFuture {
+        let _ = (url, headers);
+        Box::pin(futures_util::future::ready(Ok(None)))
+    }
+
+    /// Starts authenticating a connection to `host`, which offered the
+    /// authentication `schemes`, like `"Negotiate"` or `"NTLM"`.
+    ///
+    /// Returns `None` if none of the schemes is supported.
+    fn start(&self, host: &str, schemes: &[&str]) -> Option<Box<dyn AuthExchange>> {
+        let _ = (host, schemes);
+        None
+    }
+}
+
+/// The client's part of authenticating one connection.
+pub trait AuthExchange: Send {
+    /// The scheme of the tokens, one of those the server offered.
+    fn scheme(&self) -> &str;
+
+    /// Returns the next token to send, given the token of the server's last
+    /// challenge, which is `None` for the first token.
+    fn step(
+        &mut self,
+        challenge: Option<&[u8]>,
+    ) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync>>;
+}
+
+/// The schemes and tokens of the challenges in some response headers.
+pub(crate) struct Challenges(Vec<(String, Option<Vec<u8>>)>);
+
+impl Challenges {
+    /// Parses the challenges in the `name` headers, skipping their
+    /// parameters, like the `realm` of `Basic`.
+    pub(crate) fn parse(headers: &HeaderMap, name: HeaderName) -> Challenges {
+        let mut challenges = Vec::new();
+        for value in headers.get_all(name) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for item in split_unquoted(value) {
+                let mut words = item.split_whitespace();
+                let scheme = match words.next() {
+                    // Parameters of the previous challenge.
+                    Some(scheme) if !scheme.contains('=') => scheme,
+                    _ => continue,
+                };
+                let token = match (words.next(), words.next()) {
+                    (Some(token), None) if !token.trim_end_matches('=').contains('=') => {
+                        base64::decode(token).ok()
+                    }
+                    _ => None,
+                };
+                challenges.push((scheme.to_owned(), token));
+            }
+        }
+        Challenges(challenges)
+    }
+
+    fn schemes(&self) -> Vec<&str> {
+        self.0.iter().map(|(scheme, _)| &scheme[..]).collect()
+    }
+
+    fn token(&self, scheme: &str) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(scheme))
+            .and_then(|(_, token)| token.as_ref())
+            .map(|token| &token[..])
+    }
+}
+
+/// Splits a header value at the commas outside of quoted strings.
+fn split_unquoted(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+/// An exchange in progress, producing `Authorization` header values.
+pub(crate) struct Exchange {
+    inner: Box<dyn AuthExchange>,
+    legs: usize,
+}
+
+impl Exchange {
+    /// Starts an exchange with `host` if `provider` supports one of the
+    /// `challenges`.
+    pub(crate) fn start(
+        provider: &dyn AuthProvider,
+        host: &str,
+        challenges: &Challenges,
+    ) -> Option<Exchange> {
+        let inner = provider.start(host, &challenges.schemes())?;
+        log::debug!("authenticating with {} using {}", host, inner.scheme());
+        Some(Exchange { inner, legs: 0 })
+    }
+
+    /// Returns the header value answering `challenges`, or the first one for
+    /// `None`.
+    ///
+    /// Returns `Ok(None)` if the server rejected the last token.
+    pub(crate) fn respond(
+        &mut self,
+        challenges: Option<&Challenges>,
+    ) -> Result<Option<HeaderValue>, Box<dyn StdError + Send + Sync>> {
+        let challenge = match challenges {
+            Some(challenges) => match challenges.token(self.inner.scheme()) {
+                Some(token) => Some(token),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        if self.legs == MAX_LEGS {
+            return Ok(None);
+        }
+        self.legs += 1;
+
+        let token = self.inner.step(challenge)?;
+        let value = format!("{} {}", self.inner.scheme(), base64::encode(&token));
+        Ok(Some(HeaderValue::from_str(&value)?))
+    }
+}
+
+impl fmt::Debug for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Exchange")
+            .field("scheme", &self.inner.scheme())
+            .field("legs", &self.legs)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::WWW_AUTHENTICATE;
+
+    struct Echo;
+
+    impl AuthProvider for Echo {
+        fn start(&self, _host: &str, schemes: &[&str]) -> Option<Box<dyn AuthExchange>> {
+            if schemes.contains(&"Echo") {
+                Some(Box::new(Echo))
+            } else {
+                None
+            }
+        }
+    }
+
+    impl AuthExchange for Echo {
+        fn scheme(&self) -> &str {
+            "Echo"
+        }
+
+        fn step(
+            &mut self,
+            challenge: Option<&[u8]>,
+        ) -> Result<Vec<u8>, Box<dyn StdError + Send + Sync>> {
+            Ok(challenge.unwrap_or(b"hello").to_vec())
+        }
+    }
+
+    fn challenges(values: &[&'static str]) -> Challenges {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(WWW_AUTHENTICATE, HeaderValue::from_static(value));
+        }
+        Challenges::parse(&headers, WWW_AUTHENTICATE)
+    }
+
+    #[test]
+    fn test_parse_challenges() {
+        let parsed = challenges(&[
+            r#"Basic realm="a, b", charset="UTF-8", Negotiate"#,
+            "NTLM aGk=",
+        ]);
+        assert_eq!(parsed.schemes(), vec!["Basic", "Negotiate", "NTLM"]);
+        assert_eq!(parsed.token("Basic"), None);
+        assert_eq!(parsed.token("Negotiate"), None);
+        assert_eq!(parsed.token("ntlm"), Some(&b"hi"[..]));
+    }
+
+    #[test]
+    fn test_exchange_legs() {
+        assert!(Exchange::start(&Echo, "example.com", &challenges(&["NTLM"])).is_none());
+
+        let mut exchange = Exchange::start(&Echo, "example.com", &challenges(&["Echo"])).unwrap();
+        assert_eq!(exchange.respond(None).unwrap().unwrap(), "Echo aGVsbG8=");
+        assert_eq!(
+            exchange.respond(Some(&challenges(&["Echo aGk="]))).unwrap().unwrap(),
+            "Echo aGk="
+        );
+        // A challenge without a token rejects the last one.
+        assert!(exchange.respond(Some(&challenges(&["Echo"]))).unwrap().is_none());
+
+        for _ in 2..MAX_LEGS {
+            exchange.respond(Some(&challenges(&["Echo aGk="]))).unwrap().unwrap();
+        }
+        assert!(exchange.respond(Some(&challenges(&["Echo aGk="]))).unwrap().is_none());
+    }
+}
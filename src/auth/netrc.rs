@@ -0,0 +1,237 @@
+//! Credentials from `.netrc` files, like curl's `--netrc`.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use http::header::HeaderValue;
+
+use crate::proxy::encode_basic_auth;
+
+/// A trait for the arguments of `ClientBuilder::netrc`: `true` to read the
+/// user's `.netrc`, `false` to read none, or the path of a file to read.
+///
+/// This trait is "sealed", such that only types within reqwest can
+/// implement it.
+pub trait IntoNetrc: IntoNetrcSealed {}
+
+impl<T: IntoNetrcSealed> IntoNetrc for T {}
+
+pub trait IntoNetrcSealed {
+    fn into_netrc(self) -> crate::Result<Option<Netrc>>;
+}
+
+impl IntoNetrcSealed for bool {
+    fn into_netrc(self) -> crate::Result<Option<Netrc>> {
+        if !self {
+            return Ok(None);
+        }
+        let path = match default_path() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(Netrc::parse(&content))),
+            // Like curl, a missing `.netrc` just has no credentials.
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(crate::error::builder(err)),
+        }
+    }
+}
+
+impl IntoNetrcSealed for &Path {
+    fn into_netrc(self) -> crate::Result<Option<Netrc>> {
+        let content = fs::read_to_string(self).map_err(crate::error::builder)?;
+        Ok(Some(Netrc::parse(&content)))
+    }
+}
+
+impl IntoNetrcSealed for &PathBuf {
+    fn into_netrc(self) -> crate::Result<Option<Netrc>> {
+        self.as_path().into_netrc()
+    }
+}
+
+impl IntoNetrcSealed for PathBuf {
+    fn into_netrc(self) -> crate::Result<Option<Netrc>> {
+        self.as_path().into_netrc()
+    }
+}
+
+/// The file named by `NETRC`, or else the `.netrc` (`_netrc` on Windows) in
+/// the home directory.
+fn default_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC") {
+        return Some(PathBuf::from(path));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+    Some(Path::new(&home).join(name))
+}
+
+/// The credentials of a `.netrc` file.
+#[derive(Clone, Debug, Default)]
+pub struct Netrc {
+    machines: Vec<Machine>,
+}
+
+#[derive(Clone, Debug)]
+struct Machine {
+    /// `None` for the `default` entry.
+    name: Option<String>,
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Netrc {
+    pub(crate) fn parse(content: &str) -> Netrc {
+        let mut machines: Vec<Machine> = Vec::new();
+        let mut tokens = tokens(content).into_iter();
+        while let Some(token) = tokens.next() {
+            let machine = match &token[..] {
+                "machine" => match tokens.next() {
+                    Some(name) => Machine {
+                        name: Some(name),
+                        login: None,
+                        password: None,
+                    },
+                    None => break,
+                },
+                "default" => Machine {
+                    name: None,
+                    login: None,
+                    password: None,
+                },
+                "login" | "password" => {
+                    let value = tokens.next();
+                    if let Some(machine) = machines.last_mut() {
+                        if token == "login" {
+                            machine.login = value;
+                        } else {
+                            machine.password = value;
+                        }
+                    }
+                    continue;
+                }
+                "account" => {
+                    tokens.next();
+                    continue;
+                }
+                _ => continue,
+            };
+            machines.push(machine);
+        }
+        Netrc { machines }
+    }
+
+    /// The `Authorization` header for `host`, from its `machine` entry, or
+    /// else the `default` entry.
+    pub(crate) fn authorization(&self, host: &str) -> Option<HeaderValue> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let machine = self
+            .machines
+            .iter()
+            .find(|machine| match machine.name {
+                Some(ref name) => name.eq_ignore_ascii_case(host),
+                None => false,
+            })
+            .or_else(|| self.machines.iter().find(|machine| machine.name.is_none()))?;
+        let login = machine.login.as_ref()?;
+        let password = match machine.password {
+            Some(ref password) => &password[..],
+            None => "",
+        };
+        Some(encode_basic_auth(login, password))
+    }
+}
+
+/// Splits `content` into tokens, skipping comments and the bodies of
+/// `macdef` macros, which end at an empty line.
+fn tokens(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut in_macro = false;
+    for line in content.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        let mut chars = line.chars().peekable();
+        loop {
+            while let Some(&c) = chars.peek() {
+                if !c.is_whitespace() {
+                    break;
+                }
+                chars.next();
+            }
+            let mut token = String::new();
+            match chars.peek() {
+                None | Some('#') => break,
+                Some('"') => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => token.extend(chars.next()),
+                            c => token.push(c),
+                        }
+                    }
+                }
+                Some(_) => {
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() {
+                            break;
+                        }
+                        token.push(c);
+                        chars.next();
+                    }
+                }
+            }
+            if token == "macdef" {
+                // The name of the macro ends the line.
+                in_macro = true;
+                break;
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_netrc() {
+        let netrc = Netrc::parse(
+            "# comment\n\
+             machine example.com login alice password \"p w\\\"d\"\n\
+             macdef init\n\
+             machine evil.com login mallory\n\
+             \n\
+             machine other.com\n  login bob\n  account acct\n  password s3cr3t\n\
+             default login anonymous\n",
+        );
+        assert_eq!(
+            netrc.authorization("EXAMPLE.com").unwrap(),
+            encode_basic_auth("alice", "p w\"d")
+        );
+        assert_eq!(
+            netrc.authorization("other.com").unwrap(),
+            encode_basic_auth("bob", "s3cr3t")
+        );
+        // The macro body isn't parsed.
+        assert_eq!(
+            netrc.authorization("evil.com").unwrap(),
+            encode_basic_auth("anonymous", "")
+        );
+    }
+
+    #[test]
+    fn test_netrc_without_default() {
+        let netrc = Netrc::parse("machine example.com login alice password secret");
+        assert!(netrc.authorization("example.org").is_none());
+        assert!(Netrc::parse("").authorization("example.com").is_none());
+    }
+}
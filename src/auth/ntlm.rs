@@ -0,0 +1,365 @@
+//! NTLMv2 authentication, as specified by [MS-NLMP].
+//!
+//! [MS-NLMP]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-nlmp
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+
+use super::{AuthExchange, AuthProvider};
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+const SIGNATURE: &[u8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+const NEGOTIATE_FLAGS: u32 = NEGOTIATE_UNICODE
+    | NEGOTIATE_OEM
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+/// The `AvId` of `MsvAvEOL`, ending the target info of a challenge.
+const AV_EOL: u16 = 0;
+/// The `AvId` of `MsvAvTimestamp`, the server's time.
+const AV_TIMESTAMP: u16 = 7;
+
+/// 100 nanosecond intervals between 1601-01-01 and the Unix epoch.
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// Answers `NTLM` challenges, and `Negotiate` challenges of servers that
+/// accept NTLM tokens for them, with NTLMv2.
+///
+/// # Example
+///
+/// ```
+/// # fn run() -> Result<(), Box<std::error::Error>> {
+/// use std::sync::Arc;
+/// use reqwest::auth::Ntlm;
+///
+/// let ntlm = Arc::new(Ntlm::new("CORP\\alice", "secret"));
+/// let proxy = reqwest::Proxy::all("http://proxy.corp.example:8080")?
+///     .auth(ntlm.clone());
+/// let client = reqwest::Client::builder()
+///     .proxy(proxy)
+///     .auth(ntlm)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Ntlm {
+    user: String,
+    domain: String,
+    password: String,
+}
+
+impl Ntlm {
+    /// Creates NTLM credentials, where `username` may name the domain as
+    /// well, like `DOMAIN\user`.
+    pub fn new(username: &str, password: &str) -> Ntlm {
+        let (domain, user) = match username.find('\\') {
+            Some(i) => (&username[..i], &username[i + 1..]),
+            None => ("", username),
+        };
+        Ntlm {
+            user: user.to_owned(),
+            domain: domain.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    /// The NTLMv2 hash of these credentials.
+    fn ntowfv2(&self) -> [u8; 16] {
+        let mut identity = utf16(&self.user.to_uppercase());
+        identity.extend(utf16(&self.domain));
+        hmac_md5(&md4(&utf16(&self.password)), &identity)
+    }
+
+    /// The `AUTHENTICATE_MESSAGE` answering `challenge`, a
+    /// `CHALLENGE_MESSAGE`.
+    fn authenticate(
+        &self,
+        challenge: &[u8],
+        client_challenge: [u8; 8],
+        now: u64,
+    ) -> Result<Vec<u8>, BoxError> {
+        if challenge.len() < 32 || !challenge.starts_with(SIGNATURE) || le32(challenge, 8) != 2 {
+            return Err("invalid NTLM challenge".into());
+        }
+        let flags = le32(challenge, 20);
+        let server_challenge = &challenge[24..32];
+        let target_info = if flags & NEGOTIATE_TARGET_INFO != 0 && challenge.len() >= 48 {
+            let len = le16(challenge, 40) as usize;
+            let offset = le32(challenge, 44) as usize;
+            offset
+                .checked_add(len)
+                .and_then(|end| challenge.get(offset..end))
+                .ok_or("invalid NTLM challenge target info")?
+        } else {
+            &[]
+        };
+        let timestamp = server_timestamp(target_info);
+
+        let key = self.ntowfv2();
+
+        let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+        blob.extend_from_slice(&timestamp.unwrap_or(now).to_le_bytes());
+        blob.extend_from_slice(&client_challenge);
+        blob.extend_from_slice(&[0; 4]);
+        blob.extend_from_slice(target_info);
+        blob.extend_from_slice(&[0; 4]);
+
+        let mut nt = hmac_md5(&key, &[server_challenge, &blob].concat()).to_vec();
+        nt.extend_from_slice(&blob);
+
+        // The LMv2 response must be empty when the server sent its time.
+        let lm = if timestamp.is_some() {
+            vec![0; 24]
+        } else {
+            let mut lm = hmac_md5(&key, &[server_challenge, &client_challenge].concat()).to_vec();
+            lm.extend_from_slice(&client_challenge);
+            lm
+        };
+
+        let encode = |s: &str| {
+            if flags & NEGOTIATE_UNICODE != 0 {
+                utf16(s)
+            } else {
+                s.as_bytes().to_vec()
+            }
+        };
+        let domain = encode(&self.domain);
+        let user = encode(&self.user);
+
+        let fields: [&[u8]; 6] = [&lm, &nt, &domain, &user, &[], &[]];
+        let mut msg = SIGNATURE.to_vec();
+        msg.extend_from_slice(&3u32.to_le_bytes());
+        let mut offset = 64;
+        for field in fields.iter() {
+            msg.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            msg.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += field.len();
+        }
+        msg.extend_from_slice(&(flags & NEGOTIATE_FLAGS).to_le_bytes());
+        for field in fields.iter() {
+            msg.extend_from_slice(field);
+        }
+        Ok(msg)
+    }
+}
+
+impl fmt::Debug for Ntlm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Ntlm")
+            .field("user", &self.user)
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+impl AuthProvider for Ntlm {
+    fn start(&self, _host: &str, schemes: &[&str]) -> Option<Box<dyn AuthExchange>> {
+        let scheme = *["NTLM", "Negotiate"]
+            .iter()
+            .find(|scheme| schemes.iter().any(|offered| offered.eq_ignore_ascii_case(scheme)))?;
+        Some(Box::new(NtlmExchange {
+            scheme,
+            credentials: self.clone(),
+            negotiated: false,
+        }))
+    }
+}
+
+struct NtlmExchange {
+    scheme: &'static str,
+    credentials: Ntlm,
+    negotiated: bool,
+}
+
+impl AuthExchange for NtlmExchange {
+    fn scheme(&self) -> &str {
+        self.scheme
+    }
+
+    fn step(&mut self, challenge: Option<&[u8]>) -> Result<Vec<u8>, BoxError> {
+        match challenge {
+            None if !self.negotiated => {
+                self.negotiated = true;
+                Ok(negotiate())
+            }
+            Some(challenge) if self.negotiated => {
+                let mut client_challenge = [0; 8];
+                getrandom::getrandom(&mut client_challenge)
+                    .map_err(|e| format!("NTLM client challenge: {}", e))?;
+                self.credentials.authenticate(challenge, client_challenge, filetime_now())
+            }
+            _ => Err("unexpected NTLM challenge".into()),
+        }
+    }
+}
+
+/// The `NEGOTIATE_MESSAGE` starting an exchange.
+fn negotiate() -> Vec<u8> {
+    let mut msg = SIGNATURE.to_vec();
+    msg.extend_from_slice(&1u32.to_le_bytes());
+    msg.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // No domain or workstation.
+    msg.extend_from_slice(&[0; 16]);
+    msg
+}
+
+/// The `MsvAvTimestamp` of a challenge's target info.
+fn server_timestamp(mut target_info: &[u8]) -> Option<u64> {
+    while target_info.len() >= 4 {
+        let id = le16(target_info, 0);
+        let len = le16(target_info, 2) as usize;
+        let value = target_info.get(4..4 + len)?;
+        match id {
+            AV_EOL => return None,
+            AV_TIMESTAMP if len == 8 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(value);
+                return Some(u64::from_le_bytes(bytes));
+            }
+            _ => target_info = &target_info[4 + len..],
+        }
+    }
+    None
+}
+
+/// The time as a Windows `FILETIME`.
+fn filetime_now() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    FILETIME_UNIX_EPOCH + now.as_secs() * 10_000_000 + u64::from(now.subsec_nanos()) / 100
+}
+
+fn le16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn le32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|c| c.to_le_bytes().to_vec()).collect()
+}
+
+fn md4(input: &[u8]) -> [u8; 16] {
+    Md4::digest(input).into()
+}
+
+fn hmac_md5(key: &[u8], msg: &[u8]) -> [u8; 16] {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(msg);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_digests() {
+        assert_eq!(hex(&md4(b"abc")), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(
+            hex(&hmac_md5(&[0x0b; 16], b"Hi There")),
+            "9294727a3638bb1c13f48ef8158bfc9d"
+        );
+    }
+
+    #[test]
+    fn test_ntowfv2() {
+        // From MS-NLMP 4.2.4.1.1.
+        let ntlm = Ntlm::new("Domain\\User", "Password");
+        assert_eq!(hex(&md4(&utf16("Password"))), "a4f49c406510bdcab6824ee7c30fd852");
+        assert_eq!(hex(&ntlm.ntowfv2()), "0c868a403bfd7a93a3001ef22ef02e3f");
+    }
+
+    #[test]
+    fn test_exchange() {
+        let ntlm = Ntlm::new("Domain\\User", "Password");
+        assert!(ntlm.start("example.com", &["Basic"]).is_none());
+        let mut exchange = ntlm.start("example.com", &["Negotiate", "NTLM"]).unwrap();
+        assert_eq!(exchange.scheme(), "NTLM");
+
+        let msg = exchange.step(None).unwrap();
+        assert!(msg.starts_with(SIGNATURE));
+        assert_eq!(le32(&msg, 8), 1);
+        assert_eq!(le32(&msg, 12), NEGOTIATE_FLAGS);
+
+        let target_info = [2, 0, 12, 0, b'D', 0, b'o', 0, b'm', 0, b'a', 0, b'i', 0, b'n', 0, 0, 0, 0, 0];
+        let mut challenge = SIGNATURE.to_vec();
+        challenge.extend_from_slice(&2u32.to_le_bytes());
+        challenge.extend_from_slice(&[0, 0, 0, 0, 48, 0, 0, 0]);
+        challenge.extend_from_slice(&(NEGOTIATE_UNICODE | NEGOTIATE_NTLM | NEGOTIATE_TARGET_INFO).to_le_bytes());
+        challenge.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        challenge.extend_from_slice(&[0; 8]);
+        challenge.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+        challenge.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+        challenge.extend_from_slice(&48u32.to_le_bytes());
+        challenge.extend_from_slice(&target_info);
+
+        let msg = ntlm.authenticate(&challenge, [9; 8], 42).unwrap();
+        assert!(msg.starts_with(SIGNATURE));
+        assert_eq!(le32(&msg, 8), 3);
+        let field = |at: usize| {
+            let len = le16(&msg, at) as usize;
+            let offset = le32(&msg, at + 4) as usize;
+            &msg[offset..offset + len]
+        };
+        assert_eq!(field(28), &utf16("Domain")[..]);
+        assert_eq!(field(36), &utf16("User")[..]);
+        assert_eq!(le32(&msg, 60), NEGOTIATE_UNICODE | NEGOTIATE_NTLM | NEGOTIATE_TARGET_INFO);
+
+        let nt = field(20);
+        let blob = &nt[16..];
+        assert_eq!(&blob[8..16], &42u64.to_le_bytes());
+        assert_eq!(&blob[16..24], &[9; 8]);
+        assert_eq!(&blob[28..28 + target_info.len()], &target_info[..]);
+        let proof = hmac_md5(&ntlm.ntowfv2(), &[&[1, 2, 3, 4, 5, 6, 7, 8], blob].concat());
+        assert_eq!(&nt[..16], &proof);
+        assert_eq!(field(12).len(), 24);
+
+        assert!(exchange.step(Some(b"garbage")).is_err());
+
+        // Target info past the end of the challenge.
+        challenge[44..48].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ntlm.authenticate(&challenge, [9; 8], 42).is_err());
+    }
+
+    #[test]
+    fn test_server_timestamp() {
+        let mut target_info = vec![1, 0, 2, 0, b'S', 0, 7, 0, 8, 0];
+        target_info.extend_from_slice(&1234u64.to_le_bytes());
+        target_info.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(server_timestamp(&target_info), Some(1234));
+        assert_eq!(server_timestamp(&[0, 0, 0, 0]), None);
+        assert_eq!(server_timestamp(&[7, 0, 8, 0, 1]), None);
+    }
+}
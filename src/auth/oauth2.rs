@@ -0,0 +1,308 @@
+//! OAuth 2.0 bearer tokens, from the client credentials and refresh token
+//! grants of RFC 6749.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{self, BoxFuture, FutureExt, Shared};
+use http::header::{HeaderMap, HeaderValue};
+
+use super::{AuthFuture, AuthProvider};
+use crate::{Client, IntoUrl, Url};
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// A token request, which every request needing a new token waits for.
+type Fetch = Shared<BoxFuture<'static, Result<HeaderValue, String>>>;
+
+/// How long before their expiry tokens are replaced, so they don't expire
+/// while a request is on its way.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(10);
+
+/// Authorizes requests with bearer tokens from an OAuth 2.0 token endpoint.
+///
+/// Tokens are only sent to the hosts added with `allow_host`, and requests
+/// to other hosts go without one. They are cached until they expire, or until
+/// an allowed server answers a request with `401 Unauthorized`, when a new
+/// token is fetched and the request is sent once more.
+///
+/// # Example
+///
+/// ```
+/// # fn run() -> Result<(), reqwest::Error> {
+/// use std::sync::Arc;
+/// use reqwest::auth::OAuth2;
+///
+/// let oauth2 = OAuth2::client_credentials(
+///     "https://login.example.com/oauth2/token",
+///     "my-service",
+///     "s3cr3t",
+/// )?
+/// .scope("orders:read")
+/// .allow_host("api.example.com");
+/// let client = reqwest::Client::builder()
+///     .auth(Arc::new(oauth2))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OAuth2 {
+    config: Arc<Config>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Clone)]
+struct Config {
+    token_url: Url,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    /// Lowercase hosts the tokens are sent to.
+    hosts: Vec<String>,
+    client: Client,
+}
+
+struct State {
+    /// Given by the refresh token grant, which may rotate it.
+    refresh_token: Option<String>,
+    token: Option<Token>,
+    fetching: Option<Fetch>,
+}
+
+struct Token {
+    authorization: HeaderValue,
+    expires: Option<Instant>,
+}
+
+impl OAuth2 {
+    /// Gets tokens with the client credentials grant, authenticating the
+    /// client at `token_url` with `client_id` and `client_secret`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `token_url` isn't a valid URL, or if the client
+    /// for token requests can't be built.
+    pub fn client_credentials<U: IntoUrl>(
+        token_url: U,
+        client_id: &str,
+        client_secret: &str,
+    ) -> crate::Result<OAuth2> {
+        OAuth2::new(token_url, client_id, client_secret, None)
+    }
+
+    /// Gets tokens with the refresh token grant, authenticating the client
+    /// at `token_url` with `client_id` and `client_secret`.
+    ///
+    /// When the token endpoint rotates the refresh token, the new one is used
+    /// for later tokens.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `token_url` isn't a valid URL, or if the client
+    /// for token requests can't be built.
+    pub fn refresh_token<U: IntoUrl>(
+        token_url: U,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> crate::Result<OAuth2> {
+        OAuth2::new(token_url, client_id, client_secret, Some(refresh_token.to_owned()))
+    }
+
+    fn new<U: IntoUrl>(
+        token_url: U,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: Option<String>,
+    ) -> crate::Result<OAuth2> {
+        Ok(OAuth2 {
+            config: Arc::new(Config {
+                token_url: token_url.into_url()?,
+                client_id: client_id.to_owned(),
+                client_secret: client_secret.to_owned(),
+                scope: None,
+                hosts: Vec::new(),
+                client: Client::builder().build()?,
+            }),
+            state: Arc::new(Mutex::new(State {
+                refresh_token,
+                token: None,
+                fetching: None,
+            })),
+        })
+    }
+
+    /// Asks for tokens with `scope`, a space separated list of scopes.
+    pub fn scope(mut self, scope: &str) -> OAuth2 {
+        Arc::make_mut(&mut self.config).scope = Some(scope.to_owned());
+        self
+    }
+
+    /// Sends tokens with the requests to `host`.
+    ///
+    /// The host is matched exactly and case-insensitively, on any port.
+    /// Without any allowed host, no request gets a token.
+    pub fn allow_host(mut self, host: &str) -> OAuth2 {
+        Arc::make_mut(&mut self.config)
+            .hosts
+            .push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Uses `client` for the token requests, instead of a default `Client`.
+    ///
+    /// It shouldn't be a client with this provider.
+    pub fn client(mut self, client: Client) -> OAuth2 {
+        Arc::make_mut(&mut self.config).client = client;
+        self
+    }
+
+    /// Whether the tokens may be sent with a request to `url`.
+    fn allows(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => self
+                .config
+                .hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host)),
+            None => false,
+        }
+    }
+
+    /// Returns the `Authorization` header of a token, fetching a new token
+    /// if there is no fresh one cached or if `renew` is set.
+    ///
+    /// Only one token is fetched at a time, which all callers share.
+    fn token(&self, renew: bool) -> AuthFuture {
+        let config = self.config.clone();
+        let state = self.state.clone();
+        Box::pin(async move {
+            let fetching = {
+                let mut locked = state.lock().unwrap();
+                if let Some(ref token) = locked.token {
+                    let fresh = match token.expires {
+                        Some(expires) => Instant::now() + EXPIRY_MARGIN < expires,
+                        None => true,
+                    };
+                    if fresh && !renew {
+                        return Ok(Some(token.authorization.clone()));
+                    }
+                }
+                match locked.fetching {
+                    Some(ref fetching) => fetching.clone(),
+                    None => {
+                        let refresh_token = locked.refresh_token.clone();
+                        let fetching = renew_token(config, Arc::downgrade(&state), refresh_token)
+                            .boxed()
+                            .shared();
+                        locked.fetching = Some(fetching.clone());
+                        fetching
+                    }
+                }
+            };
+            Ok(Some(fetching.await?))
+        })
+    }
+}
+
+/// Fetches a new token and caches it in `state`.
+async fn renew_token(
+    config: Arc<Config>,
+    state: Weak<Mutex<State>>,
+    refresh_token: Option<String>,
+) -> Result<HeaderValue, String> {
+    let fetched = fetch(&config, refresh_token.as_ref()).await;
+    let state = match state.upgrade() {
+        Some(state) => state,
+        None => return fetched.map(|(token, _)| token.authorization).map_err(|e| e.to_string()),
+    };
+    let mut state = state.lock().unwrap();
+    state.fetching = None;
+    let (token, rotated) = fetched.map_err(|e| e.to_string())?;
+    let authorization = token.authorization.clone();
+    state.token = Some(token);
+    if refresh_token.is_some() && rotated.is_some() {
+        state.refresh_token = rotated;
+    }
+    Ok(authorization)
+}
+
+/// Requests a token from the token endpoint, returning it with the refresh
+/// token of the response.
+async fn fetch(
+    config: &Config,
+    refresh_token: Option<&String>,
+) -> Result<(Token, Option<String>), BoxError> {
+    let mut form = Vec::new();
+    match refresh_token {
+        Some(refresh_token) => {
+            form.push(("grant_type", "refresh_token"));
+            form.push(("refresh_token", refresh_token));
+        }
+        None => form.push(("grant_type", "client_credentials")),
+    }
+    if let Some(ref scope) = config.scope {
+        form.push(("scope", scope));
+    }
+
+    log::debug!("requesting OAuth2 token from {}", config.token_url);
+    let res = config
+        .client
+        .post(config.token_url.clone())
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()?;
+    let body: serde_json::Value = res.json().await?;
+
+    let access_token = body["access_token"]
+        .as_str()
+        .ok_or("OAuth2 token response without access_token")?;
+    let expires_in = match body["expires_in"] {
+        serde_json::Value::Number(ref secs) => secs.as_u64(),
+        // Some servers send numbers as strings.
+        serde_json::Value::String(ref secs) => secs.parse().ok(),
+        _ => None,
+    };
+    let mut authorization = HeaderValue::from_str(&format!("Bearer {}", access_token))?;
+    authorization.set_sensitive(true);
+
+    let token = Token {
+        authorization,
+        // Expiries too far ahead to represent never come.
+        expires: expires_in.and_then(|secs| Instant::now().checked_add(Duration::from_secs(secs))),
+    };
+    Ok((token, body["refresh_token"].as_str().map(String::from)))
+}
+
+impl AuthProvider for OAuth2 {
+    fn authorization(&self, url: &Url) -> AuthFuture {
+        if !self.allows(url) {
+            return Box::pin(future::ready(Ok(None)));
+        }
+        self.token(false)
+    }
+
+    fn unauthorized(&self, url: &Url, _headers: &HeaderMap) -> AuthFuture {
+        // Neither fetch a token for, nor send one to, other hosts.
+        if !self.allows(url) {
+            return Box::pin(future::ready(Ok(None)));
+        }
+        self.token(true)
+    }
+}
+
+impl fmt::Debug for OAuth2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OAuth2")
+            .field("token_url", &self.config.token_url.as_str())
+            .field("client_id", &self.config.client_id)
+            .field("scope", &self.config.scope)
+            .field("hosts", &self.config.hosts)
+            .finish()
+    }
+}
@@ -7,20 +7,28 @@ use std::convert::TryInto;
 use std::fmt;
 use std::future::Future;
 use std::net::IpAddr;
+#[cfg(any(feature = "__tls", unix))]
+use std::path::Path;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use http::header::HeaderValue;
+use http::Uri;
+use hyper::client::connect::Connection;
+use hyper::service::Service;
 use log::{error, trace};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::{mpsc, oneshot};
 
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
 use super::wait;
-use crate::{async_impl, header, IntoUrl, Method, Proxy, redirect};
+use crate::auth::{AuthProvider, IntoNetrc};
+use crate::sign::RequestSigner;
+use crate::{async_impl, header, hsts, IntoUrl, Method, Proxy, redirect};
 #[cfg(feature = "__tls")]
-use crate::{Certificate, Identity};
+use crate::{tls, Certificate, Identity};
 
 /// A `Client` to make Requests with.
 ///
@@ -185,6 +193,25 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.cookie_store(enable))
     }
 
+    /// Enable HTTP Strict Transport Security for the client.
+    ///
+    /// Hosts that send a `Strict-Transport-Security` header over HTTPS are
+    /// remembered, and later `http://` requests to them, including redirect
+    /// targets, are sent over `https://` instead.
+    ///
+    /// By default, no HSTS store is used.
+    pub fn hsts(self, enable: bool) -> ClientBuilder {
+        self.with_inner(|inner| inner.hsts(enable))
+    }
+
+    /// Use a specific HSTS store for the client.
+    ///
+    /// This allows preloading hosts, persisting the store, or sharing it
+    /// between clients. See the [`hsts`](crate::hsts) module.
+    pub fn hsts_store(self, store: Arc<hsts::Store>) -> ClientBuilder {
+        self.with_inner(|inner| inner.hsts_store(store))
+    }
+
     /// Enable auto gzip decompression by checking the `Content-Encoding` response header.
     ///
     /// If auto gzip decompresson is turned on:
@@ -231,6 +258,50 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.referer(enable))
     }
 
+    // Authentication options
+
+    /// Authenticate requests with `provider`, like an `auth::OAuth2`.
+    ///
+    /// Requests without an `Authorization` header get the one of the
+    /// provider. When a server answers with `401 Unauthorized`, the request
+    /// is sent once more: on a new connection authenticated by exchanging
+    /// tokens with the server, if the provider supports one of its
+    /// challenges, or else with the `Authorization` header the provider
+    /// refreshed, if any. The refreshed header is only sent when the server
+    /// has the origin of the request the client was given, not after a
+    /// redirect to another one. Requests with bodies that can't be sent
+    /// again are not retried.
+    pub fn auth(self, provider: Arc<dyn AuthProvider>) -> ClientBuilder {
+        self.with_inner(move |inner| inner.auth(provider))
+    }
+
+    /// Authenticate with the credentials of a `.netrc` file, like curl.
+    ///
+    /// `true` reads the file named by the `NETRC` environment variable, or
+    /// else `.netrc` in the home directory (`_netrc` on Windows), if it
+    /// exists. A path reads that file instead, and `false` no file.
+    ///
+    /// Requests without an `Authorization` header, and proxies without
+    /// credentials, get Basic authentication with the `login` and `password`
+    /// of the `machine` entry for their host, or else of the `default` entry.
+    /// Redirects first remove the `Authorization` header like the redirect
+    /// policy's `SensitiveHeaders` say, then look up the credentials of the
+    /// next host.
+    ///
+    /// Building the `Client` fails if the file cannot be read.
+    pub fn netrc<N: IntoNetrc>(self, netrc: N) -> ClientBuilder {
+        self.with_inner(move |inner| inner.netrc(netrc))
+    }
+
+    /// Sign requests with `signer`, like a `sign::AwsSigV4`.
+    ///
+    /// Requests are signed right before they are sent, once the default
+    /// headers and cookies were added, and signed again when following
+    /// redirects. Failing to sign a request fails it.
+    pub fn signer(self, signer: Arc<dyn RequestSigner>) -> ClientBuilder {
+        self.with_inner(move |inner| inner.signer(signer))
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -383,6 +454,46 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.local_address(addr))
     }
 
+    /// Use a custom connector to open connections, instead of TCP.
+    ///
+    /// The connector is called with the URI of the server, or of the proxy
+    /// if one applies. TLS, HTTP proxies and the connect timeout are still
+    /// applied on top of the connections it returns.
+    pub fn connector<C>(self, connector: C) -> ClientBuilder
+    where
+        C: Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: AsyncRead + AsyncWrite + Connection + Send + Sync + Unpin + 'static,
+        C::Error: Into<crate::error::BoxError>,
+        C::Future: Send + 'static,
+    {
+        self.with_inner(move |inner| inner.connector(connector))
+    }
+
+    /// Send all requests over the Unix domain socket at `path`.
+    ///
+    /// Connections skip DNS, proxies and TLS. Request URLs still need an
+    /// `http` scheme and a host, which is only used for the `Host` header.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .unix_socket("/var/run/docker.sock")
+    ///     .build()?;
+    /// let containers = client.get("http://localhost/containers/json").send()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This is only available on Unix platforms.
+    #[cfg(unix)]
+    pub fn unix_socket<P: AsRef<Path>>(self, path: P) -> ClientBuilder {
+        self.with_inner(move |inner| inner.unix_socket(path))
+    }
+
     // TLS options
 
     /// Add a custom root certificate.
@@ -421,12 +532,200 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.add_root_certificate(cert))
     }
 
+    /// Add the certificates of all PEM files in a directory as custom root
+    /// certificates.
+    ///
+    /// This works with plain directories of `.pem` or `.crt` files as well as
+    /// OpenSSL style hashed directories. Files that do not contain PEM
+    /// encoded certificates are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails if the directory cannot be read.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn add_root_certificates_from_dir<P: AsRef<Path>>(self, dir: P) -> ClientBuilder {
+        self.with_inner(move |inner| inner.add_root_certificates_from_dir(dir))
+    }
+
+    /// Controls the use of the root certificates built into the TLS backend.
+    ///
+    /// Defaults to `true`. When disabled, only the certificates added with
+    /// `add_root_certificate` and the like are trusted.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_built_in_root_certs(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_built_in_root_certs(enable))
+    }
+
+    /// Trust the root certificates named by the `SSL_CERT_FILE` and
+    /// `SSL_CERT_DIR` environment variables.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_root_certs_from_env(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_root_certs_from_env(enable))
+    }
+
+    /// Pin the certificate of a host to the SHA-256 hash of its public key.
+    ///
+    /// See the async [`ClientBuilder::pin_certificate`](crate::ClientBuilder::pin_certificate)
+    /// for the accepted patterns and hash format.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn pin_certificate(self, host_pattern: &str, sha256_spki: &str) -> ClientBuilder {
+        self.with_inner(move |inner| inner.pin_certificate(host_pattern, sha256_spki))
+    }
+
+    /// Add a certificate revocation list to check server certificates
+    /// against.
+    ///
+    /// See the async [`ClientBuilder::add_crl`](crate::ClientBuilder::add_crl)
+    /// for which certificates are checked.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn add_crl(self, crl: tls::CertificateRevocationList) -> ClientBuilder {
+        self.with_inner(move |inner| inner.add_crl(crl))
+    }
+
+    /// Controls the checking of OCSP responses stapled to the TLS handshake.
+    ///
+    /// Defaults to `false`, and requires the built-in rustls backend.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_ocsp_stapling(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_ocsp_stapling(enable))
+    }
+
+    /// Set the minimum required TLS version for connections.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn min_tls_version(self, version: tls::Version) -> ClientBuilder {
+        self.with_inner(move |inner| inner.min_tls_version(version))
+    }
+
+    /// Set the maximum allowed TLS version for connections.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn max_tls_version(self, version: tls::Version) -> ClientBuilder {
+        self.with_inner(move |inner| inner.max_tls_version(version))
+    }
+
+    /// Restrict the cipher suites that may be negotiated.
+    ///
+    /// See the async [`ClientBuilder::tls_cipher_suites`](crate::ClientBuilder::tls_cipher_suites)
+    /// for the accepted names.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_cipher_suites<I>(self, suites: I) -> ClientBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.with_inner(move |inner| inner.tls_cipher_suites(suites))
+    }
+
     /// Sets the identity to be used for client certificate authentication.
     #[cfg(feature = "__tls")]
     pub fn identity(self, identity: Identity) -> ClientBuilder {
         self.with_inner(move |inner| inner.identity(identity))
     }
 
+    /// Choose the client certificate for each server that asks for one.
+    ///
+    /// The resolver is called with the host name of the server during the
+    /// handshake. When it returns `None`, the identity set with
+    /// `ClientBuilder::identity` is used, if any.
+    ///
+    /// # Errors
+    ///
+    /// Building the `Client` fails when the `native-tls` backend is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn identity_resolver<F>(self, resolver: F) -> ClientBuilder
+    where
+        F: Fn(&str) -> Option<Identity> + Send + Sync + 'static,
+    {
+        self.with_inner(move |inner| inner.identity_resolver(resolver))
+    }
+
+    /// Sets how many TLS sessions are kept for resumption.
+    ///
+    /// See the async [`ClientBuilder::tls_session_cache_size`](crate::ClientBuilder::tls_session_cache_size)
+    /// for when resumption is disabled.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_session_cache_size(self, size: usize) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_session_cache_size(size))
+    }
+
+    /// Controls whether connections tunneled through proxies share the TLS
+    /// session cache of direct connections.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_session_cache_shared(self, shared: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_session_cache_shared(shared))
+    }
+
+    /// Controls sending safe requests as TLS 1.3 early data.
+    ///
+    /// See the async [`ClientBuilder::tls_early_data`](crate::ClientBuilder::tls_early_data)
+    /// for the details.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `rustls-tls` feature to be enabled.
+    #[cfg(feature = "rustls-tls")]
+    pub fn tls_early_data(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_early_data(enable))
+    }
+
     /// Controls the use of hostname verification.
     ///
     /// Defaults to `false`.
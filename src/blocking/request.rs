@@ -3,7 +3,7 @@ use std::convert::TryFrom;
 use std::time::Duration;
 
 use base64::encode;
-use http::{Request as HttpRequest, request::Parts};
+use http::{Extensions, Request as HttpRequest, request::Parts};
 use serde::Serialize;
 #[cfg(feature = "json")]
 use serde_json;
@@ -99,6 +99,18 @@ impl Request {
         self.inner.timeout_mut()
     }
 
+    /// Get the extensions.
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        self.inner.extensions()
+    }
+
+    /// Get a mutable reference to the extensions.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        self.inner.extensions_mut()
+    }
+
     /// Attempts to clone the `Request`.
     ///
     /// None is returned if a body is which can not be cloned. This can be because the body is a
@@ -339,6 +351,30 @@ impl RequestBuilder {
         self
     }
 
+    /// Isolates this request from others through SOCKS proxies, with the
+    /// given credentials.
+    ///
+    /// Requests with different credentials don't share connections, and
+    /// SOCKS proxies get these credentials instead of their own. Tor, for
+    /// example, uses separate circuits for them. They are only sent to SOCKS
+    /// proxies, never to a server or an HTTP proxy.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `socks` feature to be enabled.
+    #[cfg(feature = "socks")]
+    pub fn socks_isolation<U, P>(mut self, username: U, password: P) -> RequestBuilder
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        if let Ok(ref mut req) = self.request {
+            let isolation = crate::proxy::SocksIsolation::new(username.into(), password.into());
+            req.extensions_mut().insert(isolation);
+        }
+        self
+    }
+
     /// Modify the query string of the URL.
     ///
     /// Modifies the URL of this request, adding the parameters provided.
@@ -601,12 +637,14 @@ impl<T> TryFrom<HttpRequest<T>> for Request where T:Into<Body> {
             method,
             uri,
             headers,
+            extensions,
             ..
         } = parts;
         let url = Url::parse(&uri.to_string())
             .map_err(crate::error::builder)?;
         let mut inner = async_impl::Request::new(method, url);
         crate::util::replace_headers(inner.headers_mut(), headers);
+        *inner.extensions_mut() = extensions;
         Ok(Request {
             body: Some(body.into()),
             inner,
@@ -614,6 +652,19 @@ impl<T> TryFrom<HttpRequest<T>> for Request where T:Into<Body> {
     }
 }
 
+impl TryFrom<Request> for HttpRequest<Body> {
+    type Error = crate::Error;
+
+    fn try_from(req: Request) -> crate::Result<Self> {
+        let Request { body, inner } = req;
+        let (parts, _) = HttpRequest::try_from(inner)?.into_parts();
+        Ok(HttpRequest::from_parts(
+            parts,
+            body.unwrap_or_else(|| Body::from(Vec::new())),
+        ))
+    }
+}
+
 impl fmt::Debug for Request {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt_request_fields(&mut f.debug_struct("Request"), self).finish()
@@ -979,6 +1030,27 @@ mod tests {
         assert_eq!(req.url().as_str(), "http://localhost/");
     }
 
+    #[test]
+    fn convert_into_http_request() {
+        let client = Client::new();
+        let req = client
+            .put("http://localhost/")
+            .header("User-Agent", "my-awesome-agent/1.0")
+            .body("test test test")
+            .build()
+            .expect("request build");
+
+        let http_request = HttpRequest::<super::Body>::try_from(req).unwrap();
+        assert_eq!(http_request.method(), Method::PUT);
+        assert_eq!(http_request.uri(), "http://localhost/");
+        assert_eq!(
+            http_request.headers()["User-Agent"],
+            "my-awesome-agent/1.0"
+        );
+        let test_data = b"test test test";
+        assert_eq!(http_request.body().as_bytes(), Some(&test_data[..]));
+    }
+
     #[test]
     fn test_basic_auth_sensitive_header() {
         let client = Client::new();
@@ -15,6 +15,9 @@ use super::client::KeepCoreThreadAlive;
 use super::wait;
 #[cfg(feature = "cookies")]
 use crate::cookie;
+use crate::redirect;
+#[cfg(feature = "__tls")]
+use crate::tls::TlsInfo;
 use crate::{async_impl, StatusCode, Url, Version};
 
 /// A Response to a submitted `Request`.
@@ -162,6 +165,23 @@ impl Response {
         self.inner.url()
     }
 
+    /// Get the redirects that were followed to get this `Response`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn run() -> Result<(), Box<std::error::Error>> {
+    /// let resp = reqwest::blocking::get("http://httpbin.org/redirect/1")?;
+    /// for hop in resp.redirect_history() {
+    ///     println!("{} redirected with {}", hop.url(), hop.status());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn redirect_history(&self) -> &[redirect::Hop] {
+        self.inner.redirect_history()
+    }
+
     /// Get the remote address used to get this `Response`.
     ///
     /// # Example
@@ -177,6 +197,35 @@ impl Response {
         self.inner.remote_addr()
     }
 
+    /// Get information about the TLS connection this `Response` was
+    /// received on.
+    ///
+    /// Returns `None` if the connection did not use TLS.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.inner.tls_info()
+    }
+
+    /// Get the extensions of this `Response`.
+    ///
+    /// This includes any extensions that were set on the `Request` that
+    /// produced it.
+    #[inline]
+    pub fn extensions(&self) -> &http::Extensions {
+        self.inner.extensions()
+    }
+
+    /// Get a mutable reference to the extensions of this `Response`.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut http::Extensions {
+        self.inner.extensions_mut()
+    }
+
     /// Get the content-length of the response, if it is known.
     ///
     /// Reasons it may not be known:
@@ -5,14 +5,20 @@ use hyper::client::connect::{Connected, Connection};
 use tokio::io::{AsyncRead, AsyncWrite};
 #[cfg(feature = "native-tls-crate")]
 use native_tls_crate::{TlsConnector, TlsConnectorBuilder};
+use http::header::{HeaderMap, PROXY_AUTHORIZATION};
 #[cfg(feature = "__tls")]
-use http::header::HeaderValue;
-use futures_util::future::Either;
+use http::header::{HeaderName, HeaderValue};
+#[cfg(feature = "__tls")]
+use http::StatusCode;
+#[cfg(feature = "__tls")]
+use crate::auth::{AuthProvider, Challenges, Exchange};
 use bytes::{Buf, BufMut};
 
 use std::future::Future;
 use std::io;
 use std::net::IpAddr;
+#[cfg(unix)]
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -23,19 +29,38 @@ use pin_project_lite::pin_project;
 #[cfg(feature = "trust-dns")]
 use crate::dns::TrustDnsResolver;
 use crate::proxy::{Proxy, ProxyScheme};
+#[cfg(feature = "socks")]
+use crate::proxy::SocksIsolation;
 use crate::error::BoxError;
+#[cfg(feature = "__tls")]
+use crate::tls::{CertificatePins, RevocationLists};
+#[cfg(feature = "rustls-tls")]
+use crate::tls::IdentityResolver;
 #[cfg(feature = "default-tls")]
 use self::native_tls_conn::NativeTlsConn;
 #[cfg(feature = "rustls-tls")]
-use self::rustls_tls_conn::RustlsTlsConn;
+use self::rustls_tls_conn::{EarlyDataConn, RustlsTlsConn};
+use self::proxy_headers::ProxyHeaders;
+#[cfg(feature = "rustls-tls")]
+use std::collections::HashMap;
+#[cfg(feature = "rustls-tls")]
+use std::sync::Mutex;
 
 #[derive(Clone)]
 pub(crate) enum HttpConnector {
     Gai(hyper::client::HttpConnector),
     #[cfg(feature = "trust-dns")]
     TrustDns(hyper::client::HttpConnector<TrustDnsResolver>),
+    Custom(CustomConnector),
 }
 
+/// A user supplied connector, type erased so it can stand in for TCP.
+type CustomConnector = Arc<
+    dyn Fn(Uri) -> Pin<Box<dyn Future<Output = Result<BoxConn, BoxError>> + Send>>
+        + Send
+        + Sync,
+>;
+
 impl HttpConnector {
     pub(crate) fn new_gai() -> Self {
         Self::Gai(hyper::client::HttpConnector::new())
@@ -48,18 +73,39 @@ impl HttpConnector {
             .map(Self::TrustDns)
             .map_err(crate::error::builder)
     }
+
+    pub(crate) fn new_custom<C>(connector: C) -> Self
+    where
+        C: Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: AsyncRead + AsyncWrite + Connection + Send + Sync + Unpin + 'static,
+        C::Error: Into<BoxError>,
+        C::Future: Send + 'static,
+    {
+        Self::Custom(Arc::new(move |dst| {
+            let mut connector = connector.clone();
+            Box::pin(async move {
+                futures_util::future::poll_fn(|cx| connector.poll_ready(cx))
+                    .await
+                    .map_err(Into::into)?;
+                let io = connector.call(dst).await.map_err(Into::into)?;
+                Ok(Box::new(io) as BoxConn)
+            })
+        }))
+    }
 }
 
 macro_rules! impl_http_connector {
-    ($(fn $name:ident(&mut self, $($par_name:ident: $par_type:ty),*)$( -> $return:ty)?;)+) => {
+    ($(fn $name:ident(&mut self, $($par_name:ident: $par_type:ty),*);)+) => {
         #[allow(dead_code)]
         impl HttpConnector {
             $(
-                fn $name(&mut self, $($par_name: $par_type),*)$( -> $return)? {
+                fn $name(&mut self, $($par_name: $par_type),*) {
                     match self {
                         Self::Gai(resolver) => resolver.$name($($par_name),*),
                         #[cfg(feature = "trust-dns")]
                         Self::TrustDns(resolver) => resolver.$name($($par_name),*),
+                        // Custom connectors are configured by their owner.
+                        Self::Custom(_) => (),
                     }
                 }
             )+
@@ -74,32 +120,136 @@ impl_http_connector! {
 }
 
 impl Service<Uri> for HttpConnector {
-    type Response = <hyper::client::HttpConnector as Service<Uri>>::Response;
-    type Error = <hyper::client::HttpConnector as Service<Uri>>::Error;
-    #[cfg(feature = "trust-dns")]
-    type Future = Either<
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
-        <hyper::client::HttpConnector<TrustDnsResolver> as Service<Uri>>::Future,
-    >;
-    #[cfg(not(feature = "trust-dns"))]
-    type Future = Either<
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
-    >;
+    type Response = HttpStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<HttpStream, BoxError>> + Send>>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         match self {
-            Self::Gai(resolver) => resolver.poll_ready(cx),
+            Self::Gai(resolver) => resolver.poll_ready(cx).map_err(Into::into),
             #[cfg(feature = "trust-dns")]
-            Self::TrustDns(resolver) => resolver.poll_ready(cx),
+            Self::TrustDns(resolver) => resolver.poll_ready(cx).map_err(Into::into),
+            Self::Custom(_) => Poll::Ready(Ok(())),
         }
     }
 
     fn call(&mut self, dst: Uri) -> Self::Future {
         match self {
-            Self::Gai(resolver) => Either::Left(resolver.call(dst)),
+            Self::Gai(resolver) => {
+                let connecting = resolver.call(dst);
+                Box::pin(async move { Ok(HttpStream::Tcp(connecting.await?)) })
+            }
             #[cfg(feature = "trust-dns")]
-            Self::TrustDns(resolver) => Either::Right(resolver.call(dst)),
+            Self::TrustDns(resolver) => {
+                let connecting = resolver.call(dst);
+                Box::pin(async move { Ok(HttpStream::Tcp(connecting.await?)) })
+            }
+            Self::Custom(connect) => {
+                let connecting = connect(dst);
+                Box::pin(async move { Ok(HttpStream::Custom(connecting.await?)) })
+            }
+        }
+    }
+}
+
+/// The transport under TLS and proxies: a TCP stream, or whatever a custom
+/// connector returned.
+pub(crate) enum HttpStream {
+    Tcp(tokio::net::TcpStream),
+    Custom(BoxConn),
+}
+
+impl HttpStream {
+    #[cfg(feature = "__tls")]
+    fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        match self {
+            HttpStream::Tcp(tcp) => tcp.set_nodelay(nodelay),
+            HttpStream::Custom(_) => Ok(()),
+        }
+    }
+}
+
+impl Connection for HttpStream {
+    fn connected(&self) -> Connected {
+        match self {
+            HttpStream::Tcp(tcp) => tcp.connected(),
+            HttpStream::Custom(io) => io.connected(),
+        }
+    }
+}
+
+impl AsyncRead for HttpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8]
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_read(cx, buf),
+            HttpStream::Custom(io) => Pin::new(io).poll_read(cx, buf),
+        }
+    }
+
+    unsafe fn prepare_uninitialized_buffer(
+        &self,
+        buf: &mut [MaybeUninit<u8>]
+    ) -> bool {
+        match self {
+            HttpStream::Tcp(tcp) => tcp.prepare_uninitialized_buffer(buf),
+            HttpStream::Custom(io) => io.prepare_uninitialized_buffer(buf),
+        }
+    }
+
+    fn poll_read_buf<B: BufMut>(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut B
+    ) -> Poll<io::Result<usize>>
+        where
+            Self: Sized
+    {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_read_buf(cx, buf),
+            HttpStream::Custom(io) => Pin::new(io).poll_read_buf(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for HttpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8]
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_write(cx, buf),
+            HttpStream::Custom(io) => Pin::new(io).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_flush(cx),
+            HttpStream::Custom(io) => Pin::new(io).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_shutdown(cx),
+            HttpStream::Custom(io) => Pin::new(io).poll_shutdown(cx),
+        }
+    }
+
+    fn poll_write_buf<B: Buf>(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut B
+    ) -> Poll<io::Result<usize>> where
+        Self: Sized {
+        match self.get_mut() {
+            HttpStream::Tcp(tcp) => Pin::new(tcp).poll_write_buf(cx, buf),
+            HttpStream::Custom(io) => Pin::new(io).poll_write_buf(cx, buf),
         }
     }
 }
@@ -114,6 +264,25 @@ pub(crate) struct Connector {
     nodelay: bool,
     #[cfg(feature = "__tls")]
     user_agent: Option<HeaderValue>,
+    #[cfg(feature = "__tls")]
+    pins: Arc<CertificatePins>,
+    #[cfg(feature = "__tls")]
+    revocation_lists: Arc<RevocationLists>,
+    /// TLS with each of `proxies` that has its own settings, by index.
+    #[cfg(feature = "__tls")]
+    proxy_tls: Arc<Vec<Option<ProxyTls>>>,
+    #[cfg(feature = "rustls-tls")]
+    identity_resolver: Option<IdentityResolver>,
+    #[cfg(feature = "rustls-tls")]
+    early_data: bool,
+    /// Whether the last handshake with a host negotiated HTTP/2, which a
+    /// connection sending early data has to assume before its handshake is
+    /// done.
+    #[cfg(feature = "rustls-tls")]
+    early_protocols: Arc<Mutex<HashMap<String, bool>>>,
+    /// Credentials to use with SOCKS proxies instead of their own.
+    #[cfg(feature = "socks")]
+    isolation: Option<SocksIsolation>,
 }
 
 #[derive(Clone)]
@@ -128,6 +297,18 @@ enum Inner {
         tls: Arc<rustls::ClientConfig>,
         tls_proxy: Arc<rustls::ClientConfig>,
     },
+    #[cfg(unix)]
+    Unix(Arc<Path>),
+}
+
+/// TLS with a proxy, built from its `ProxyTlsConfig` for the client's backend.
+#[cfg(feature = "__tls")]
+#[derive(Clone)]
+enum ProxyTls {
+    #[cfg(feature = "default-tls")]
+    Native(TlsConnector),
+    #[cfg(feature = "rustls-tls")]
+    Rustls(Arc<rustls::ClientConfig>),
 }
 
 impl Connector {
@@ -148,6 +329,8 @@ impl Connector {
             verbose: verbose::OFF,
             proxies,
             timeout: None,
+            #[cfg(feature = "socks")]
+            isolation: None,
         }
     }
 
@@ -195,6 +378,17 @@ impl Connector {
             timeout: None,
             nodelay,
             user_agent,
+            pins: Arc::new(CertificatePins::default()),
+            revocation_lists: Arc::new(RevocationLists::default()),
+            proxy_tls: Arc::new(Vec::new()),
+            #[cfg(feature = "rustls-tls")]
+            identity_resolver: None,
+            #[cfg(feature = "rustls-tls")]
+            early_data: false,
+            #[cfg(feature = "rustls-tls")]
+            early_protocols: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "socks")]
+            isolation: None,
         }
     }
 
@@ -233,6 +427,17 @@ impl Connector {
             timeout: None,
             nodelay,
             user_agent,
+            pins: Arc::new(CertificatePins::default()),
+            revocation_lists: Arc::new(RevocationLists::default()),
+            proxy_tls: Arc::new(Vec::new()),
+            #[cfg(feature = "rustls-tls")]
+            identity_resolver: None,
+            #[cfg(feature = "rustls-tls")]
+            early_data: false,
+            #[cfg(feature = "rustls-tls")]
+            early_protocols: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "socks")]
+            isolation: None,
         }
     }
 
@@ -244,6 +449,216 @@ impl Connector {
         self.verbose.0 = enabled;
     }
 
+    /// Sends every connection to the Unix domain socket at `path` instead,
+    /// without DNS, proxies or TLS.
+    #[cfg(unix)]
+    pub(crate) fn set_unix_socket(&mut self, path: Arc<Path>) {
+        self.inner = Inner::Unix(path);
+    }
+
+    #[cfg(feature = "__tls")]
+    pub(crate) fn set_certificate_pins(&mut self, pins: CertificatePins) {
+        #[cfg(feature = "rustls-tls")]
+        {
+            if !pins.is_empty() {
+                self.disable_rustls_resumption();
+            }
+        }
+        self.pins = Arc::new(pins);
+    }
+
+    #[cfg(feature = "__tls")]
+    pub(crate) fn set_revocation_lists(&mut self, crls: RevocationLists) {
+        #[cfg(feature = "rustls-tls")]
+        {
+            if !crls.is_empty() {
+                self.disable_rustls_resumption();
+            }
+        }
+        self.revocation_lists = Arc::new(crls);
+    }
+
+    /// Builds TLS with the proxies that have their own settings.
+    #[cfg(feature = "__tls")]
+    pub(crate) fn build_proxy_tls(&mut self) -> crate::Result<()> {
+        let mut proxy_tls = Vec::with_capacity(self.proxies.len());
+        for proxy in self.proxies.iter() {
+            let config = match proxy.get_tls_config() {
+                Some(config) => config,
+                None => {
+                    proxy_tls.push(None);
+                    continue;
+                }
+            };
+            let tls = match self.inner {
+                #[cfg(feature = "default-tls")]
+                Inner::DefaultTls(..) => Some(ProxyTls::Native(config.build_native_tls()?)),
+                #[cfg(feature = "rustls-tls")]
+                Inner::RustlsTls { .. } => Some(ProxyTls::Rustls(Arc::new(config.build_rustls()?))),
+                // Proxies aren't used over a unix socket.
+                #[cfg(unix)]
+                Inner::Unix(_) => None,
+            };
+            proxy_tls.push(tls);
+        }
+        self.proxy_tls = Arc::new(proxy_tls);
+        Ok(())
+    }
+
+    /// Resumed rustls sessions don't carry the server's certificates, so
+    /// they can't be checked against pins or revocation lists.
+    #[cfg(feature = "rustls-tls")]
+    fn disable_rustls_resumption(&mut self) {
+        match self.inner {
+            Inner::RustlsTls {
+                ref mut tls,
+                ref mut tls_proxy,
+                ..
+            } => {
+                Arc::make_mut(tls).set_persistence(crate::tls::rustls_session_cache(0));
+                Arc::make_mut(tls_proxy).set_persistence(crate::tls::rustls_session_cache(0));
+            }
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => (),
+            #[cfg(unix)]
+            Inner::Unix(_) => (),
+        }
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn set_identity_resolver(&mut self, resolver: IdentityResolver) -> crate::Result<()> {
+        match self.inner {
+            Inner::RustlsTls { .. } => {
+                self.identity_resolver = Some(resolver);
+                Ok(())
+            }
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => Err(crate::error::builder(
+                "client certificate resolvers are only supported with rustls",
+            )),
+            #[cfg(unix)]
+            Inner::Unix(_) => Ok(()),
+        }
+    }
+
+    /// Gives connections through proxies their own session cache of `size`
+    /// sessions, instead of sharing the one of direct connections.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn separate_proxy_session_cache(&mut self, size: usize) {
+        match self.inner {
+            Inner::RustlsTls { ref mut tls_proxy, .. } => {
+                Arc::make_mut(tls_proxy).set_persistence(crate::tls::rustls_session_cache(size));
+            }
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => (),
+            #[cfg(unix)]
+            Inner::Unix(_) => (),
+        }
+    }
+
+    /// A connector that sends TLS 1.3 early data on direct connections
+    /// resuming a session, or `None` if the TLS backend can't.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn early_data(&self) -> Option<Connector> {
+        match self.inner {
+            Inner::RustlsTls {
+                ref http,
+                ref tls,
+                ref tls_proxy,
+            } => {
+                let mut early = (**tls).clone();
+                early.enable_early_data = true;
+                Some(Connector {
+                    inner: Inner::RustlsTls {
+                        http: http.clone(),
+                        tls: Arc::new(early),
+                        tls_proxy: tls_proxy.clone(),
+                    },
+                    early_data: true,
+                    ..self.clone()
+                })
+            }
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => None,
+            #[cfg(unix)]
+            Inner::Unix(_) => None,
+        }
+    }
+
+    /// A connector using `isolation` with SOCKS proxies, keeping its
+    /// connections apart from those of other requests.
+    #[cfg(feature = "socks")]
+    pub(crate) fn isolated(&self, isolation: SocksIsolation) -> Connector {
+        Connector {
+            isolation: Some(isolation),
+            ..self.clone()
+        }
+    }
+
+    /// The rustls config to connect to `host` with.
+    #[cfg(feature = "rustls-tls")]
+    fn rustls_config(
+        &self,
+        tls: &Arc<rustls::ClientConfig>,
+        host: &str,
+    ) -> Arc<rustls::ClientConfig> {
+        match self.identity_resolver {
+            Some(ref resolver) => {
+                let host = host.trim_matches(|c| c == '[' || c == ']');
+                crate::tls::rustls_with_identity_resolver(tls, resolver, host)
+            }
+            None => tls.clone(),
+        }
+    }
+
+    #[cfg(feature = "default-tls")]
+    async fn native_tls_handshake<T>(
+        &self,
+        tls: &TlsConnector,
+        host: &str,
+        io: T,
+    ) -> Result<NativeTlsConn<T>, BoxError>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let host = host.trim_matches(|c| c == '[' || c == ']');
+        let conn = native_tls_conn::connect(tls, host, io).await?;
+        // Only the end-entity certificate is available from native-tls.
+        self.pins
+            .verify(host, || conn.peer_certificate().into_iter().collect())?;
+        self.revocation_lists
+            .verify(host, || conn.peer_certificate().into_iter().collect())?;
+        Ok(conn)
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    fn verify_rustls_peer(
+        &self,
+        host: &str,
+        session: &rustls::ClientSession,
+    ) -> Result<(), BoxError> {
+        use rustls::Session;
+
+        let host = host.trim_matches(|c| c == '[' || c == ']');
+        self.pins.verify(host, || {
+            session
+                .get_peer_certificates()
+                .into_iter()
+                .flatten()
+                .map(|cert| cert.0)
+                .collect()
+        })?;
+        self.revocation_lists.verify(host, || {
+            session
+                .get_peer_certificates()
+                .into_iter()
+                .flatten()
+                .map(|cert| cert.0)
+                .collect()
+        })?;
+        Ok(())
+    }
+
     #[cfg(feature = "socks")]
     async fn connect_socks(
         &self,
@@ -251,10 +666,16 @@ impl Connector {
         proxy: ProxyScheme,
     ) -> Result<Conn, BoxError> {
         let dns = match proxy {
-            ProxyScheme::Socks5 {
+            ProxyScheme::Socks4 {
+                remote_dns: false, ..
+            }
+            | ProxyScheme::Socks5 {
                 remote_dns: false, ..
             } => socks::DnsResolve::Local,
-            ProxyScheme::Socks5 {
+            ProxyScheme::Socks4 {
+                remote_dns: true, ..
+            }
+            | ProxyScheme::Socks5 {
                 remote_dns: true, ..
             } => socks::DnsResolve::Proxy,
             ProxyScheme::Http { .. } | ProxyScheme::Https { .. } => {
@@ -271,12 +692,9 @@ impl Connector {
                         .ok_or("no host in url")?
                         .to_string();
                     let conn = socks::connect(proxy, dst, dns).await?;
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                    let io = tls_connector
-                        .connect(&host, conn)
-                        .await?;
+                    let io = self.native_tls_handshake(tls, &host, conn).await?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(NativeTlsConn { inner: io }),
+                        inner: self.verbose.wrap(io),
                         is_proxy: false,
                     });
                 }
@@ -287,11 +705,11 @@ impl Connector {
                     use tokio_rustls::webpki::DNSNameRef;
                     use tokio_rustls::TlsConnector as RustlsConnector;
 
-                    let tls = tls_proxy.clone();
                     let host = dst
                         .host()
                         .ok_or("no host in url")?
                         .to_string();
+                    let tls = self.rustls_config(tls_proxy, &host);
                     let conn = socks::connect(proxy, dst, dns).await?;
                     let dnsname = DNSNameRef::try_from_ascii_str(&host)
                         .map(|dnsname| dnsname.to_owned())
@@ -299,14 +717,17 @@ impl Connector {
                     let io = RustlsConnector::from(tls)
                         .connect(dnsname.as_ref(), conn)
                         .await?;
+                    self.verify_rustls_peer(&host, io.get_ref().1)?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(RustlsTlsConn { inner: io }),
+                        inner: self.verbose.wrap(RustlsTlsConn { inner: io, host }),
                         is_proxy: false,
                     });
                 }
             }
             #[cfg(not(feature = "__tls"))]
-            Inner::Http(_) => ()
+            Inner::Http(_) => (),
+            #[cfg(unix)]
+            Inner::Unix(_) => (),
         }
 
         socks::connect(proxy, dst, dns).await.map(|tcp| Conn {
@@ -320,9 +741,10 @@ impl Connector {
         dst: Uri,
         is_proxy: bool,
     ) -> Result<Conn, BoxError> {
-        match self.inner {
+        match &self.inner {
             #[cfg(not(feature = "__tls"))]
-            Inner::Http(mut http) => {
+            Inner::Http(http) => {
+                let mut http = http.clone();
                 let io = http.call(dst).await?;
                 Ok(Conn {
                     inner: self.verbose.wrap(io),
@@ -341,14 +763,20 @@ impl Connector {
                     http.set_nodelay(true);
                 }
 
-                let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
-                let io = http.call(dst).await?;
+                let is_https = dst.scheme() == Some(&Scheme::HTTPS);
+                let host = dst.host().unwrap_or("").to_owned();
+                let tcp = http.call(dst).await?;
 
-                if let hyper_tls::MaybeHttpsStream::Https(stream) = &io {
-                    if !self.nodelay {
-                        stream.get_ref().set_nodelay(false)?;
-                    }
+                if !is_https {
+                    return Ok(Conn {
+                        inner: self.verbose.wrap(tcp),
+                        is_proxy,
+                    });
+                }
+
+                let io = self.native_tls_handshake(tls, &host, tcp).await?;
+                if !self.nodelay {
+                    io.get_ref().set_nodelay(false)?;
                 }
 
                 Ok(Conn {
@@ -367,21 +795,78 @@ impl Connector {
                     http.set_nodelay(true);
                 }
 
-                let mut http = hyper_rustls::HttpsConnector::from((http, tls.clone()));
-                let io = http.call(dst).await?;
+                let host = dst.host().unwrap_or("").to_owned();
+                let tls = self.rustls_config(tls, &host);
 
-                if let hyper_rustls::MaybeHttpsStream::Https(stream) = &io {
+                if self.early_data && dst.scheme() == Some(&Scheme::HTTPS) {
+                    use rustls::Session;
+                    use tokio_rustls::webpki::DNSNameRef;
+                    use tokio_rustls::TlsConnector as RustlsConnector;
+
+                    let dnsname = DNSNameRef::try_from_ascii_str(&host)
+                        .map(|dnsname| dnsname.to_owned())
+                        .map_err(|_| "Invalid DNS Name")?;
+                    let tcp = http.call(dst).await?;
+                    // Resolves before the handshake is done if early data
+                    // can be sent, which then goes out with the first write.
+                    let mut io = RustlsConnector::from(tls)
+                        .early_data(true)
+                        .connect(dnsname.as_ref(), tcp)
+                        .await?;
                     if !self.nodelay {
-                        let (io, _) = stream.get_ref();
-                        io.set_nodelay(false)?;
+                        io.get_ref().0.set_nodelay(false)?;
+                    }
+                    if io.get_ref().1.is_handshaking() {
+                        let h2 = self.early_protocols.lock().unwrap().get(&host).cloned();
+                        if let Some(h2) = h2 {
+                            return Ok(Conn {
+                                inner: self.verbose.wrap(EarlyDataConn::new(io, host, h2, self.clone())),
+                                is_proxy,
+                            });
+                        }
+                        // The protocol the server picks is not known, so
+                        // finish the handshake instead of sending early data.
+                        tokio::io::AsyncWriteExt::flush(&mut io).await?;
                     }
+                    let session = io.get_ref().1;
+                    self.verify_rustls_peer(&host, session)?;
+                    let h2 = session.get_alpn_protocol() == Some(b"h2");
+                    self.early_protocols.lock().unwrap().insert(host.clone(), h2);
+                    return Ok(Conn {
+                        inner: self.verbose.wrap(RustlsTlsConn { inner: io, host }),
+                        is_proxy,
+                    });
                 }
 
+                let mut http = hyper_rustls::HttpsConnector::from((http, tls));
+                let io = match http.call(dst).await? {
+                    hyper_rustls::MaybeHttpsStream::Https(stream) => {
+                        let (io, session) = stream.get_ref();
+                        self.verify_rustls_peer(&host, session)?;
+                        if !self.nodelay {
+                            io.set_nodelay(false)?;
+                        }
+                        self.verbose.wrap(RustlsTlsConn { inner: stream, host })
+                    }
+                    hyper_rustls::MaybeHttpsStream::Http(tcp) => self.verbose.wrap(tcp),
+                };
+
                 Ok(Conn {
-                    inner: self.verbose.wrap(io),
+                    inner: io,
                     is_proxy,
                 })
             }
+            #[cfg(unix)]
+            Inner::Unix(path) => {
+                if dst.scheme() == Some(&Scheme::HTTPS) {
+                    return Err("https is not supported over a unix socket".into());
+                }
+                let io = unix::connect(path).await?;
+                Ok(Conn {
+                    inner: self.verbose.wrap(io),
+                    is_proxy: false,
+                })
+            }
         }
     }
 
@@ -389,6 +874,7 @@ impl Connector {
         self,
         dst: Uri,
         proxy_scheme: ProxyScheme,
+        _proxy: usize,
     ) -> Result<Conn, BoxError> {
         log::debug!("proxy({:?}) intercepts '{:?}'", proxy_scheme, dst);
 
@@ -396,39 +882,77 @@ impl Connector {
             ProxyScheme::Http { host, auth } => (into_uri(Scheme::HTTP, host), auth),
             ProxyScheme::Https { host, auth } => (into_uri(Scheme::HTTPS, host), auth),
             #[cfg(feature = "socks")]
-            ProxyScheme::Socks5 { .. } => return self.connect_socks(dst, proxy_scheme).await,
+            ProxyScheme::Socks4 { .. } | ProxyScheme::Socks5 { .. } => {
+                return self.connect_socks(dst, proxy_scheme).await
+            }
         };
 
 
         #[cfg(feature = "__tls")]
         let auth = _auth;
+        #[cfg(feature = "__tls")]
+        let proxy_tls = self.proxy_tls.get(_proxy).and_then(|tls| tls.clone());
+        #[cfg(feature = "__tls")]
+        let headers = self.proxies[_proxy].get_custom_http_headers().clone();
+        #[cfg(feature = "__tls")]
+        let proxies = self.proxies.clone();
+        #[cfg(feature = "__tls")]
+        let proxy_auth = proxies[_proxy].get_auth();
 
         match &self.inner {
             #[cfg(feature = "default-tls")]
             Inner::DefaultTls(http, tls) => {
-                if dst.scheme() == Some(&Scheme::HTTPS) {
-                    let host = dst.host().to_owned();
-                    let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
-                    let http = http.clone();
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                    let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
+                let is_https = dst.scheme() == Some(&Scheme::HTTPS);
+                let is_https_proxy = proxy_dst.scheme() == Some(&Scheme::HTTPS);
+                if is_https || is_https_proxy {
+                    let proxy_host = proxy_dst.host().unwrap_or("").to_owned();
+                    let mut http = http.clone();
                     let conn = http.call(proxy_dst).await?;
+                    if is_https_proxy {
+                        let proxy_tls = match proxy_tls {
+                            Some(ProxyTls::Native(ref proxy_tls)) => proxy_tls,
+                            _ => tls,
+                        };
+                        let conn = self.native_tls_handshake(proxy_tls, &proxy_host, conn).await?;
+                        if !is_https {
+                            return Ok(Conn {
+                                inner: self.verbose.wrap(conn),
+                                is_proxy: true,
+                            });
+                        }
+                        let (host, port) = tunnel_dst(&dst)?;
+                        log::trace!("tunneling HTTPS over HTTPS proxy");
+                        let tunneled = tunnel(
+                            conn,
+                            host.clone(),
+                            port,
+                            self.user_agent.clone(),
+                            auth,
+                            headers,
+                            proxy_auth.map(|provider| (&**provider, &proxy_host[..])),
+                        )
+                        .await?;
+                        let io = self.native_tls_handshake(tls, &host, tunneled).await?;
+                        return Ok(Conn {
+                            inner: self.verbose.wrap(io),
+                            is_proxy: false,
+                        });
+                    }
+                    let (host, port) = tunnel_dst(&dst)?;
                     log::trace!("tunneling HTTPS over proxy");
                     let tunneled = tunnel(
                         conn,
-                        host
-                            .ok_or("no host in url")?
-                            .to_string(),
+                        host.clone(),
                         port,
                         self.user_agent.clone(),
-                        auth
-                    ).await?;
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                    let io = tls_connector
-                        .connect(&host.ok_or("no host in url")?, tunneled)
-                        .await?;
+                        auth,
+                        headers,
+                        proxy_auth.map(|provider| (&**provider, &proxy_host[..])),
+                    )
+                    .await?;
+                    let io = self.native_tls_handshake(tls, &host, tunneled).await?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(NativeTlsConn { inner: io }),
+                        inner: self.verbose.wrap(io),
                         is_proxy: false,
                     });
                 }
@@ -439,41 +963,143 @@ impl Connector {
                 tls,
                 tls_proxy,
             } => {
-                if dst.scheme() == Some(&Scheme::HTTPS) {
+                let is_https = dst.scheme() == Some(&Scheme::HTTPS);
+                let is_https_proxy = proxy_dst.scheme() == Some(&Scheme::HTTPS);
+                if is_https || is_https_proxy {
                     use tokio_rustls::webpki::DNSNameRef;
                     use tokio_rustls::TlsConnector as RustlsConnector;
 
-                    let host = dst
-                        .host()
-                        .ok_or("no host in url")?
-                        .to_string();
-                    let port = dst.port().map(|r| r.as_u16()).unwrap_or(443);
-                    let http = http.clone();
-                    let mut http = hyper_rustls::HttpsConnector::from((http, tls_proxy.clone()));
-                    let tls = tls.clone();
+                    let proxy_host = proxy_dst.host().unwrap_or("").to_owned();
+                    let proxy_tls = match proxy_tls {
+                        Some(ProxyTls::Rustls(proxy_tls)) => proxy_tls,
+                        _ => self.rustls_config(tls_proxy, &proxy_host),
+                    };
+                    let mut http = hyper_rustls::HttpsConnector::from((http.clone(), proxy_tls));
                     let conn = http.call(proxy_dst).await?;
+                    if let hyper_rustls::MaybeHttpsStream::Https(stream) = &conn {
+                        self.verify_rustls_peer(&proxy_host, stream.get_ref().1)?;
+                    }
+                    if !is_https {
+                        let io = match conn {
+                            hyper_rustls::MaybeHttpsStream::Https(stream) => {
+                                self.verbose.wrap(RustlsTlsConn { inner: stream, host: proxy_host })
+                            }
+                            hyper_rustls::MaybeHttpsStream::Http(tcp) => self.verbose.wrap(tcp),
+                        };
+                        return Ok(Conn {
+                            inner: io,
+                            is_proxy: true,
+                        });
+                    }
+
+                    let (host, port) = tunnel_dst(&dst)?;
+                    let tls = self.rustls_config(tls, &host);
                     log::trace!("tunneling HTTPS over proxy");
                     let maybe_dnsname = DNSNameRef::try_from_ascii_str(&host)
                         .map(|dnsname| dnsname.to_owned())
                         .map_err(|_| "Invalid DNS Name");
-                    let tunneled = tunnel(conn, host, port, self.user_agent.clone(), auth).await?;
+                    let tunneled = tunnel(
+                        conn,
+                        host.clone(),
+                        port,
+                        self.user_agent.clone(),
+                        auth,
+                        headers,
+                        proxy_auth.map(|provider| (&**provider, &proxy_host[..])),
+                    )
+                    .await?;
                     let dnsname = maybe_dnsname?;
                     let io = RustlsConnector::from(tls)
                         .connect(dnsname.as_ref(), tunneled)
                         .await?;
+                    self.verify_rustls_peer(&host, io.get_ref().1)?;
 
                     return Ok(Conn {
-                        inner: self.verbose.wrap(RustlsTlsConn { inner: io }),
+                        inner: self.verbose.wrap(RustlsTlsConn { inner: io, host }),
                         is_proxy: false,
                     });
                 }
             }
             #[cfg(not(feature = "__tls"))]
             Inner::Http(_) => (),
+            #[cfg(unix)]
+            Inner::Unix(_) => (),
         }
 
         self.connect_with_maybe_proxy(proxy_dst, true).await
     }
+
+    async fn connect_via_found(
+        self,
+        dst: Uri,
+        found: Option<(usize, Vec<Option<ProxyScheme>>)>,
+    ) -> Result<Conn, BoxError> {
+        match found {
+            Some((proxy, chain)) => self.connect_via_chain(dst, chain, proxy).await,
+            None => {
+                let timeout = self.timeout;
+                with_timeout(self.connect_with_maybe_proxy(dst, false), timeout).await
+            }
+        }
+    }
+
+    /// The headers for requests sent to `proxy_scheme` as plain HTTP
+    /// requests: its credentials, and the custom headers of the `Proxy`.
+    fn proxy_http_headers(&self, proxy_scheme: &ProxyScheme, proxy: usize) -> HeaderMap {
+        let mut headers = self.proxies[proxy].get_custom_http_headers().clone();
+        match proxy_scheme {
+            ProxyScheme::Http { auth: Some(auth), .. }
+            | ProxyScheme::Https { auth: Some(auth), .. }
+                if !headers.contains_key(PROXY_AUTHORIZATION) =>
+            {
+                headers.insert(PROXY_AUTHORIZATION, auth.clone());
+            }
+            _ => (),
+        }
+        headers
+    }
+
+    /// Connects through the first proxy of `chain` that works, where `None`
+    /// means connecting directly. Each attempt has the full connect timeout.
+    async fn connect_via_chain(
+        self,
+        dst: Uri,
+        chain: Vec<Option<ProxyScheme>>,
+        proxy: usize,
+    ) -> Result<Conn, BoxError> {
+        let last = chain.len() - 1;
+        for (i, proxy_scheme) in chain.into_iter().enumerate() {
+            let attempt = match proxy_scheme {
+                Some(proxy_scheme) => {
+                    let headers = self.proxy_http_headers(&proxy_scheme, proxy);
+                    let connecting = self.clone().connect_via_proxy(dst.clone(), proxy_scheme, proxy);
+                    with_timeout(connecting, self.timeout)
+                        .await
+                        .map(|conn| conn.with_proxy_headers(headers))
+                }
+                None => {
+                    let connecting = self.clone().connect_with_maybe_proxy(dst.clone(), false);
+                    with_timeout(connecting, self.timeout).await
+                }
+            };
+            match attempt {
+                Ok(conn) => return Ok(conn),
+                Err(err) if i < last => {
+                    log::debug!("proxy connect to {:?} failed, trying the next one: {}", dst, err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        unreachable!("proxy chains aren't empty")
+    }
+}
+
+/// The host and port to ask a proxy to tunnel to.
+#[cfg(feature = "__tls")]
+fn tunnel_dst(dst: &Uri) -> Result<(String, u16), BoxError> {
+    let host = dst.host().ok_or("no host in url")?.to_string();
+    let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
+    Ok((host, port))
 }
 
 fn into_uri(scheme: Scheme, host: Authority) -> Uri {
@@ -490,7 +1116,7 @@ async fn with_timeout<T, F>(f: F, timeout: Option<Duration>) -> Result<T, BoxErr
 where
     F: Future<Output = Result<T, BoxError>>,
 {
-    if let Some(to) = timeout {
+    let res = if let Some(to) = timeout {
         match tokio::time::timeout(to, f).await {
             Err(_elapsed) => Err(Box::new(crate::error::TimedOut) as BoxError),
             Ok(Ok(try_res)) => Ok(try_res),
@@ -498,7 +1124,11 @@ where
         }
     } else {
         f.await
-    }
+    };
+    // rustls reports stapled OCSP revocations as a generic handshake error.
+    #[cfg(feature = "rustls-tls")]
+    let res = res.map_err(crate::tls::stapled_revocation);
+    res
 }
 
 impl Service<Uri> for Connector {
@@ -512,20 +1142,63 @@ impl Service<Uri> for Connector {
 
     fn call(&mut self, dst: Uri) -> Self::Future {
         log::debug!("starting new connection: {:?}", dst);
-        let timeout = self.timeout;
-        for prox in self.proxies.iter() {
-            if let Some(proxy_scheme) = prox.intercept(&dst) {
+        #[cfg(feature = "socks")]
+        let isolation = self.isolation.clone();
+        #[cfg(unix)]
+        {
+            if let Inner::Unix(_) = self.inner {
                 return Box::pin(with_timeout(
-                    self.clone().connect_via_proxy(dst, proxy_scheme),
-                    timeout,
+                    self.clone().connect_with_maybe_proxy(dst, false),
+                    self.timeout,
                 ));
             }
         }
+        #[cfg(feature = "pac")]
+        {
+            if self.proxies.iter().any(Proxy::may_block) {
+                let connector = self.clone();
+                return Box::pin(async move {
+                    let proxies = connector.proxies.clone();
+                    let uri = dst.clone();
+                    let found = tokio::task::spawn_blocking(move || find_chain(&proxies, &uri)).await?;
+                    #[cfg(feature = "socks")]
+                    let found = isolate(found, isolation);
+                    connector.connect_via_found(dst, found).await
+                });
+            }
+        }
+        let found = find_chain(&self.proxies, &dst);
+        #[cfg(feature = "socks")]
+        let found = isolate(found, isolation);
+        Box::pin(self.clone().connect_via_found(dst, found))
+    }
+}
 
-        Box::pin(with_timeout(
-            self.clone().connect_with_maybe_proxy(dst, false),
-            timeout,
-        ))
+/// The first proxy that applies to `dst`, and the chain of proxies to try.
+fn find_chain(proxies: &[Proxy], dst: &Uri) -> Option<(usize, Vec<Option<ProxyScheme>>)> {
+    for (i, prox) in proxies.iter().enumerate() {
+        let chain = prox.intercept_chain(dst);
+        if !chain.is_empty() {
+            return Some((i, chain));
+        }
+    }
+    None
+}
+
+#[cfg(feature = "socks")]
+fn isolate(
+    found: Option<(usize, Vec<Option<ProxyScheme>>)>,
+    isolation: Option<SocksIsolation>,
+) -> Option<(usize, Vec<Option<ProxyScheme>>)> {
+    match (found, isolation) {
+        (Some((i, chain)), Some(isolation)) => {
+            let chain = chain
+                .into_iter()
+                .map(|proxy| proxy.map(|proxy| isolation.isolate(proxy)))
+                .collect();
+            Some((i, chain))
+        }
+        (found, _) => found,
     }
 }
 
@@ -547,6 +1220,20 @@ pin_project! {
     }
 }
 
+impl Conn {
+    /// Adds `headers` to the requests if this is a connection to a plain
+    /// HTTP proxy.
+    fn with_proxy_headers(self, headers: HeaderMap) -> Conn {
+        if !self.is_proxy || headers.is_empty() {
+            return self;
+        }
+        Conn {
+            inner: Box::new(ProxyHeaders::new(self, headers)),
+            is_proxy: true,
+        }
+    }
+}
+
 impl Connection for Conn {
     fn connected(&self) -> Connected {
         self.inner.connected().proxy(self.is_proxy)
@@ -627,73 +1314,190 @@ async fn tunnel<T>(
     port: u16,
     user_agent: Option<HeaderValue>,
     auth: Option<HeaderValue>,
+    headers: HeaderMap,
+    proxy_auth: Option<(&dyn AuthProvider, &str)>,
 ) -> Result<T, BoxError>
 where
     T: AsyncRead + AsyncWrite + Unpin,
 {
+    use http::header::{
+        CONNECTION, CONTENT_LENGTH, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION,
+        TRANSFER_ENCODING, USER_AGENT,
+    };
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    let mut buf = format!(
-        "\
-         CONNECT {0}:{1} HTTP/1.1\r\n\
-         Host: {0}:{1}\r\n\
-         ",
-        host, port
-    )
-    .into_bytes();
+    // An exchange answering `Proxy-Authenticate` challenges, and its latest
+    // token.
+    let mut exchange: Option<Exchange> = None;
+    let mut negotiated: Option<HeaderValue> = None;
 
+    loop {
+        let mut buf = format!(
+            "\
+             CONNECT {0}:{1} HTTP/1.1\r\n\
+             Host: {0}:{1}\r\n\
+             ",
+            host, port
+        )
+        .into_bytes();
+
+
+        // user-agent
+        if let Some(ref user_agent) = user_agent {
+            if !headers.contains_key(USER_AGENT) {
+                buf.extend_from_slice(b"User-Agent: ");
+                buf.extend_from_slice(user_agent.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+        }
 
-    // user-agent
-    if let Some(user_agent) = user_agent {
-        buf.extend_from_slice(b"User-Agent: ");
-        buf.extend_from_slice(user_agent.as_bytes());
-        buf.extend_from_slice(b"\r\n");
-    }
 
+        // proxy-authorization
+        if let Some(ref value) = negotiated {
+            buf.extend_from_slice(b"Proxy-Authorization: ");
+            buf.extend_from_slice(value.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        } else if let Some(ref value) = auth {
+            if !headers.contains_key(PROXY_AUTHORIZATION) {
+                log::debug!("tunnel to {}:{} using basic auth", host, port);
+                buf.extend_from_slice(b"Proxy-Authorization: ");
+                buf.extend_from_slice(value.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+        }
 
-    // proxy-authorization
-    if let Some(value) = auth {
-        log::debug!("tunnel to {}:{} using basic auth", host, port);
-        buf.extend_from_slice(b"Proxy-Authorization: ");
-        buf.extend_from_slice(value.as_bytes());
-        buf.extend_from_slice(b"\r\n");
-    }
+        // custom headers
+        for (name, value) in headers.iter() {
+            if name == HOST || (name == PROXY_AUTHORIZATION && negotiated.is_some()) {
+                continue;
+            }
+            buf.extend_from_slice(name.as_str().as_bytes());
+            buf.extend_from_slice(b": ");
+            buf.extend_from_slice(value.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
 
-    // headers end
-    buf.extend_from_slice(b"\r\n");
+        // headers end
+        buf.extend_from_slice(b"\r\n");
 
-    conn.write_all(&buf).await?;
+        conn.write_all(&buf).await?;
 
-    let mut buf = [0; 8192];
-    let mut pos = 0;
+        let mut buf = [0; 8192];
+        let mut pos = 0;
 
-    loop {
-        let n = conn.read(&mut buf[pos..]).await?;
+        let end = loop {
+            let n = conn.read(&mut buf[pos..]).await?;
 
-        if n == 0 {
-            return Err(tunnel_eof());
-        }
-        pos += n;
+            if n == 0 {
+                return Err(tunnel_eof());
+            }
+            pos += n;
 
-        let recvd = &buf[..pos];
-        if recvd.starts_with(b"HTTP/1.1 200") || recvd.starts_with(b"HTTP/1.0 200") {
-            if recvd.ends_with(b"\r\n\r\n") {
-                return Ok(conn);
+            let recvd = &buf[..pos];
+            let prefix = &b"HTTP/1."[..recvd.len().min(7)];
+            if !recvd.starts_with(prefix) {
+                return Err("unsuccessful tunnel".into());
+            }
+            if recvd.starts_with(b"HTTP/1.1 200") || recvd.starts_with(b"HTTP/1.0 200") {
+                if recvd.ends_with(b"\r\n\r\n") {
+                    return Ok(conn);
+                }
+            } else if let Some(end) = recvd.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end;
             }
             if pos == buf.len() {
                 return Err(
                     "proxy headers too long for tunnel".into()
                 );
             }
-        // else read more
-        } else if recvd.starts_with(b"HTTP/1.1 407") {
-            return Err(
-                "proxy authentication required".into()
-            );
+            // else read more
+        };
+
+        let (status, res_headers) = match parse_head(&buf[..end]) {
+            Some(parsed) => parsed,
+            None => return Err("unsuccessful tunnel".into()),
+        };
+        let next = match proxy_auth {
+            Some((provider, proxy_host)) if status == StatusCode::PROXY_AUTHENTICATION_REQUIRED => {
+                let challenges = Challenges::parse(&res_headers, PROXY_AUTHENTICATE);
+                match exchange {
+                    Some(ref mut exchange) => exchange.respond(Some(&challenges))?,
+                    None => match Exchange::start(provider, proxy_host, &challenges) {
+                        Some(started) => exchange.get_or_insert(started).respond(None)?,
+                        None => None,
+                    },
+                }
+            }
+            _ => None,
+        };
+
+        // The next token must be sent on this connection, after the body of
+        // this response.
+        let closes = buf.starts_with(b"HTTP/1.0")
+            || res_headers
+                .get_all(CONNECTION)
+                .iter()
+                .any(|value| value.as_bytes().eq_ignore_ascii_case(b"close"))
+            || res_headers.contains_key(TRANSFER_ENCODING);
+        let body_len = match res_headers.get(CONTENT_LENGTH) {
+            Some(len) => len.to_str().ok().and_then(|len| len.parse::<usize>().ok()),
+            None => Some(0),
+        };
+        let remaining = body_len.and_then(|len| len.checked_sub(pos - end - 4));
+        let (next, mut remaining) = match (next, remaining) {
+            (Some(next), Some(remaining)) if !closes => (next, remaining),
+            _ => return Err(Box::new(crate::error::TunnelRefused { status, headers: res_headers })),
+        };
+        while remaining > 0 {
+            let n = conn.read(&mut buf[..remaining.min(8192)]).await?;
+            if n == 0 {
+                return Err(tunnel_eof());
+            }
+            remaining -= n;
+        }
+        negotiated = Some(next);
+    }
+}
+
+/// The status and headers of `head`, a response to `CONNECT` without its
+/// final empty line.
+#[cfg(feature = "__tls")]
+fn parse_head(head: &[u8]) -> Option<(StatusCode, HeaderMap)> {
+    let mut lines = head.split(|&b| b == b'\n').map(|line| {
+        if line.ends_with(b"\r") {
+            &line[..line.len() - 1]
         } else {
-            return Err("unsuccessful tunnel".into());
+            line
+        }
+    });
+    let status = lines
+        .next()
+        .and_then(|line| line.get(9..12))
+        .and_then(|code| StatusCode::from_bytes(code).ok())?;
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let colon = match line.iter().position(|&b| b == b':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+        let name = HeaderName::from_bytes(&line[..colon]);
+        let value = HeaderValue::from_bytes(trim_ows(&line[colon + 1..]));
+        if let (Ok(name), Ok(value)) = (name, value) {
+            headers.append(name, value);
         }
     }
+    Some((status, headers))
+}
+
+fn trim_ows(mut value: &[u8]) -> &[u8] {
+    while let Some((&b' ', rest)) | Some((&b'\t', rest)) = value.split_first() {
+        value = rest;
+    }
+    while let Some((&b' ', rest)) | Some((&b'\t', rest)) = value.split_last() {
+        value = rest;
+    }
+    value
 }
 
 #[cfg(feature = "__tls")]
@@ -703,28 +1507,167 @@ fn tunnel_eof() -> BoxError {
 
 #[cfg(feature = "default-tls")]
 mod native_tls_conn {
+    use std::mem::MaybeUninit;
+    use std::{pin::Pin, task::{Context, Poll}};
+    use bytes::{Buf, BufMut};
+    use hyper::client::connect::{Connected, Connection};
+    use native_tls_crate::TlsConnector;
+    use pin_project_lite::pin_project;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio_native_tls::TlsStream;
+
+    use crate::tls::TlsInfo;
+
+    pin_project! {
+        pub(super) struct NativeTlsConn<T> {
+            #[pin] pub(super) inner: TlsStream<T>,
+            pub(super) host: String,
+        }
+    }
+
+    pub(super) async fn connect<T>(
+        tls: &TlsConnector,
+        host: &str,
+        io: T,
+    ) -> Result<NativeTlsConn<T>, native_tls_crate::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let inner = tokio_native_tls::TlsConnector::from(tls.clone())
+            .connect(host, io)
+            .await?;
+        Ok(NativeTlsConn {
+            inner,
+            host: host.to_owned(),
+        })
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> NativeTlsConn<T> {
+        pub(super) fn get_ref(&self) -> &T {
+            self.inner.get_ref().get_ref().get_ref()
+        }
+
+        /// The DER encoded certificate presented by the server.
+        pub(super) fn peer_certificate(&self) -> Option<Vec<u8>> {
+            self.inner
+                .get_ref()
+                .peer_certificate()
+                .ok()
+                .and_then(|cert| cert)
+                .and_then(|cert| cert.to_der().ok())
+        }
+    }
+
+    impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for NativeTlsConn<T> {
+        fn connected(&self) -> Connected {
+            self.get_ref()
+                .connected()
+                .extra(TlsInfo::from_native_tls(self.inner.get_ref(), &self.host))
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for NativeTlsConn<T> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8]
+        ) -> Poll<tokio::io::Result<usize>> {
+            let this = self.project();
+            AsyncRead::poll_read(this.inner, cx, buf)
+        }
+
+        unsafe fn prepare_uninitialized_buffer(
+            &self,
+            buf: &mut [MaybeUninit<u8>]
+        ) -> bool {
+            self.inner.prepare_uninitialized_buffer(buf)
+        }
+
+        fn poll_read_buf<B: BufMut>(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut B
+        ) -> Poll<tokio::io::Result<usize>>
+            where
+                Self: Sized
+        {
+            let this = self.project();
+            AsyncRead::poll_read_buf(this.inner, cx, buf)
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for NativeTlsConn<T> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8]
+        ) -> Poll<Result<usize, tokio::io::Error>> {
+            let this = self.project();
+            AsyncWrite::poll_write(this.inner, cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), tokio::io::Error>> {
+            let this = self.project();
+            AsyncWrite::poll_flush(this.inner, cx)
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            cx: &mut Context
+        ) -> Poll<Result<(), tokio::io::Error>> {
+            let this = self.project();
+            AsyncWrite::poll_shutdown(this.inner, cx)
+        }
+
+        fn poll_write_buf<B: Buf>(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut B
+        ) -> Poll<Result<usize, tokio::io::Error>> where
+            Self: Sized {
+            let this = self.project();
+            AsyncWrite::poll_write_buf(this.inner, cx, buf)
+        }
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+mod rustls_tls_conn {
+    use rustls::Session;
+    use std::io;
     use std::mem::MaybeUninit;
     use std::{pin::Pin, task::{Context, Poll}};
     use bytes::{Buf, BufMut};
     use hyper::client::connect::{Connected, Connection};
     use pin_project_lite::pin_project;
     use tokio::io::{AsyncRead, AsyncWrite};
-    use tokio_tls::TlsStream;
+    use tokio_rustls::client::TlsStream;
 
+    use super::Connector;
+    use crate::tls::{PendingTlsInfo, TlsInfo};
 
     pin_project! {
-        pub(super) struct NativeTlsConn<T> {
+        pub(super) struct RustlsTlsConn<T> {
             #[pin] pub(super) inner: TlsStream<T>,
+            pub(super) host: String,
         }
     }
 
-    impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for NativeTlsConn<T> {
+    impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for RustlsTlsConn<T> {
         fn connected(&self) -> Connected {
-            self.inner.get_ref().connected()
+            let (io, session) = self.inner.get_ref();
+            let connected = io
+                .connected()
+                .extra(TlsInfo::from_rustls(session, &self.host));
+            if session.get_alpn_protocol() == Some(b"h2") {
+                connected.negotiated_h2()
+            } else {
+                connected
+            }
         }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for NativeTlsConn<T> {
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for RustlsTlsConn<T> {
         fn poll_read(
             self: Pin<&mut Self>,
             cx: &mut Context,
@@ -754,7 +1697,7 @@ mod native_tls_conn {
         }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for NativeTlsConn<T> {
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for RustlsTlsConn<T> {
         fn poll_write(
             self: Pin<&mut Self>,
             cx: &mut Context,
@@ -787,44 +1730,76 @@ mod native_tls_conn {
             AsyncWrite::poll_write_buf(this.inner, cx, buf)
         }
     }
-}
-
-#[cfg(feature = "rustls-tls")]
-mod rustls_tls_conn {
-    use rustls::Session;
-    use std::mem::MaybeUninit;
-    use std::{pin::Pin, task::{Context, Poll}};
-    use bytes::{Buf, BufMut};
-    use hyper::client::connect::{Connected, Connection};
-    use pin_project_lite::pin_project;
-    use tokio::io::{AsyncRead, AsyncWrite};
-    use tokio_rustls::client::TlsStream;
-
 
     pin_project! {
-        pub(super) struct RustlsTlsConn<T> {
-            #[pin] pub(super) inner: TlsStream<T>,
+        /// A connection handed to hyper before its handshake is done, so
+        /// that the first request goes out as early data. The server is
+        /// verified, and its `TlsInfo` filled in, once the handshake is done,
+        /// which is before its response can be read.
+        pub(super) struct EarlyDataConn<T> {
+            #[pin] inner: TlsStream<T>,
+            host: String,
+            h2: bool,
+            info: PendingTlsInfo,
+            verifier: Option<Connector>,
         }
     }
 
-    impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for RustlsTlsConn<T> {
+    impl<T> EarlyDataConn<T> {
+        pub(super) fn new(inner: TlsStream<T>, host: String, h2: bool, verifier: Connector) -> Self {
+            EarlyDataConn {
+                inner,
+                host,
+                h2,
+                info: PendingTlsInfo::default(),
+                verifier: Some(verifier),
+            }
+        }
+    }
+
+    impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for EarlyDataConn<T> {
         fn connected(&self) -> Connected {
-            if self.inner.get_ref().1.get_alpn_protocol() == Some(b"h2") {
-                self.inner.get_ref().0.connected().negotiated_h2()
+            let connected = self.inner.get_ref().0.connected().extra(self.info.clone());
+            if self.h2 {
+                connected.negotiated_h2()
             } else {
-                self.inner.get_ref().0.connected()
+                connected
             }
         }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for RustlsTlsConn<T> {
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for EarlyDataConn<T> {
         fn poll_read(
             self: Pin<&mut Self>,
             cx: &mut Context,
             buf: &mut [u8]
         ) -> Poll<tokio::io::Result<usize>> {
-            let this = self.project();
-            AsyncRead::poll_read(this.inner, cx, buf)
+            let mut this = self.project();
+            let read = futures_core::ready!(AsyncRead::poll_read(this.inner.as_mut(), cx, buf))?;
+
+            let session = this.inner.get_ref().1;
+            if session.is_handshaking() {
+                return Poll::Ready(Ok(read));
+            }
+            if let Some(connector) = this.verifier.take() {
+                connector
+                    .verify_rustls_peer(this.host, session)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+                let h2 = session.get_alpn_protocol() == Some(b"h2");
+                connector
+                    .early_protocols
+                    .lock()
+                    .unwrap()
+                    .insert(this.host.clone(), h2);
+                if h2 != *this.h2 {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "server negotiated another protocol than assumed for early data",
+                    )));
+                }
+                this.info.set(TlsInfo::from_rustls(session, this.host));
+            }
+            Poll::Ready(Ok(read))
         }
 
         unsafe fn prepare_uninitialized_buffer(
@@ -833,21 +1808,9 @@ mod rustls_tls_conn {
         ) -> bool {
             self.inner.prepare_uninitialized_buffer(buf)
         }
-
-        fn poll_read_buf<B: BufMut>(
-            self: Pin<&mut Self>,
-            cx: &mut Context,
-            buf: &mut B
-        ) -> Poll<tokio::io::Result<usize>>
-            where
-                Self: Sized
-        {
-            let this = self.project();
-            AsyncRead::poll_read_buf(this.inner, cx, buf)
-        }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for RustlsTlsConn<T> {
+    impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for EarlyDataConn<T> {
         fn poll_write(
             self: Pin<&mut Self>,
             cx: &mut Context,
@@ -885,9 +1848,10 @@ mod rustls_tls_conn {
 #[cfg(feature = "socks")]
 mod socks {
     use std::io;
-    use std::net::ToSocketAddrs;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
 
     use http::Uri;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::TcpStream;
     use tokio_socks::tcp::Socks5Stream;
 
@@ -916,13 +1880,20 @@ mod socks {
         };
 
         if let DnsResolve::Local = dns {
-            let maybe_new_target = (host.as_str(), port).to_socket_addrs()?.next();
+            let addrs = (host.as_str(), port).to_socket_addrs()?;
+            let maybe_new_target = match proxy {
+                ProxyScheme::Socks4 { .. } => target_addr(addrs, true),
+                _ => target_addr(addrs, false),
+            };
             if let Some(new_target) = maybe_new_target {
                 host = new_target.ip().to_string();
             }
         }
 
         let (socket_addr, auth) = match proxy {
+            ProxyScheme::Socks4 { addr, user_id, .. } => {
+                return socks4(addr, &host, port, user_id.as_ref().map_or("", String::as_str)).await;
+            }
             ProxyScheme::Socks5 { addr, auth, .. } => (addr, auth),
             _ => unreachable!(),
         };
@@ -945,6 +1916,401 @@ mod socks {
 
         Ok(stream.into_inner())
     }
+
+    /// The address to connect to of those `host` resolved to, which is the
+    /// first IPv4 one if `ipv4_only` is set, as SOCKS4 can't reach others.
+    pub(super) fn target_addr<I>(addrs: I, ipv4_only: bool) -> Option<SocketAddr>
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let addrs = addrs.into_iter().collect::<Vec<_>>();
+        addrs
+            .iter()
+            .find(|addr| !ipv4_only || addr.is_ipv4())
+            .or_else(|| addrs.first())
+            .cloned()
+    }
+
+    /// Connects to `host` through a SOCKS4 proxy, or a SOCKS4A one if `host`
+    /// isn't an IPv4 address.
+    pub(super) async fn socks4(
+        proxy: SocketAddr,
+        host: &str,
+        port: u16,
+        user_id: &str,
+    ) -> Result<TcpStream, BoxError> {
+        if user_id.contains('\0') || host.contains('\0') {
+            return Err("socks connect error: NUL byte in user ID or host".into());
+        }
+
+        let mut request = vec![4, 1];
+        request.extend_from_slice(&port.to_be_bytes());
+        match host.parse::<Ipv4Addr>() {
+            Ok(ip) => {
+                request.extend_from_slice(&ip.octets());
+                request.extend_from_slice(user_id.as_bytes());
+                request.push(0);
+            }
+            Err(_) => {
+                if host.parse::<IpAddr>().is_ok() {
+                    return Err("socks connect error: SOCKS4 only supports IPv4".into());
+                }
+                // An address of 0.0.0.x asks the proxy to resolve the
+                // host name following the user ID.
+                request.extend_from_slice(&[0, 0, 0, 1]);
+                request.extend_from_slice(user_id.as_bytes());
+                request.push(0);
+                request.extend_from_slice(host.as_bytes());
+                request.push(0);
+            }
+        }
+
+        let mut stream = TcpStream::connect(proxy).await?;
+        stream.write_all(&request).await?;
+        let mut reply = [0; 8];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != 0 {
+            return Err("socks connect error: invalid reply".into());
+        }
+        match reply[1] {
+            90 => Ok(stream),
+            91 => Err("socks connect error: request rejected or failed".into()),
+            92 => Err("socks connect error: proxy could not reach the client's identd".into()),
+            93 => Err("socks connect error: user ID not confirmed by identd".into()),
+            code => Err(format!("socks connect error: unknown reply code {}", code).into()),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::path::Path;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use hyper::client::connect::{Connected, Connection};
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::UnixStream;
+
+    pub(super) struct UnixConn {
+        inner: UnixStream,
+    }
+
+    pub(super) async fn connect(path: &Path) -> io::Result<UnixConn> {
+        UnixStream::connect(path)
+            .await
+            .map(|inner| UnixConn { inner })
+    }
+
+    impl Connection for UnixConn {
+        fn connected(&self) -> Connected {
+            Connected::new()
+        }
+    }
+
+    impl AsyncRead for UnixConn {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8]
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for UnixConn {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8]
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+}
+
+/// Adds the headers for a plain HTTP proxy to the requests written to it,
+/// since it's only known which proxy of a chain took a request once the
+/// connection is made.
+mod proxy_headers {
+    use std::cmp;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use futures_core::ready;
+    use http::header::HeaderMap;
+    use hyper::client::connect::{Connected, Connection};
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    /// The first line of the HTTP/2 connection preface.
+    const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n";
+
+    pub(super) struct ProxyHeaders<T> {
+        inner: T,
+        headers: HeaderMap,
+        state: State,
+        /// Whether the connection carries something else after the body of
+        /// the current request.
+        upgrade: bool,
+        /// The request head, chunk size or trailer line being read.
+        line: Vec<u8>,
+        /// Rewritten output that isn't written to `inner` yet.
+        out: Vec<u8>,
+        written: usize,
+    }
+
+    enum State {
+        Head,
+        Body(u64),
+        ChunkSize,
+        ChunkData(u64),
+        ChunkEnd,
+        Trailers,
+        /// Not HTTP/1 anymore, everything passes through.
+        Raw,
+    }
+
+    impl<T> ProxyHeaders<T> {
+        pub(super) fn new(inner: T, headers: HeaderMap) -> Self {
+            ProxyHeaders {
+                inner,
+                headers,
+                state: State::Head,
+                upgrade: false,
+                line: Vec::new(),
+                out: Vec::new(),
+                written: 0,
+            }
+        }
+
+        #[cfg(test)]
+        pub(super) fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        fn is_framing(&self) -> bool {
+            match self.state {
+                State::Head | State::ChunkSize | State::ChunkEnd | State::Trailers => true,
+                State::Body(_) | State::ChunkData(_) | State::Raw => false,
+            }
+        }
+
+        /// Reads a byte of a request head or of the chunked framing.
+        fn push(&mut self, byte: u8) {
+            self.line.push(byte);
+            if byte != b'\n' {
+                return;
+            }
+            match self.state {
+                State::Head => {
+                    if self.line == H2_PREFACE {
+                        self.out.append(&mut self.line);
+                        self.state = State::Raw;
+                    } else if self.line.ends_with(b"\r\n\r\n") {
+                        self.finish_head();
+                    }
+                }
+                State::ChunkSize => {
+                    let size = chunk_size(&self.line);
+                    self.out.append(&mut self.line);
+                    self.state = match size {
+                        Some(0) => State::Trailers,
+                        Some(size) => State::ChunkData(size),
+                        None => State::Raw,
+                    };
+                }
+                State::ChunkEnd => {
+                    self.out.append(&mut self.line);
+                    self.state = State::ChunkSize;
+                }
+                State::Trailers => {
+                    let last = self.line == b"\r\n" || self.line == b"\n";
+                    self.out.append(&mut self.line);
+                    if last {
+                        self.finish_body();
+                    }
+                }
+                State::Body(_) | State::ChunkData(_) | State::Raw => unreachable!(),
+            }
+        }
+
+        /// Writes the head with the headers it doesn't have yet.
+        fn finish_head(&mut self) {
+            let head = self.line.split_off(0);
+            let mut lines = head.split(|&b| b == b'\n');
+            let request_line = lines.next().unwrap_or(&[]);
+            self.out.extend_from_slice(request_line);
+            self.out.push(b'\n');
+            let rest = &head[cmp::min(request_line.len() + 1, head.len())..];
+
+            let mut names = Vec::new();
+            let mut content_length = 0;
+            let mut chunked = false;
+            self.upgrade = false;
+            for line in lines {
+                let line = if line.ends_with(b"\r") {
+                    &line[..line.len() - 1]
+                } else {
+                    line
+                };
+                let colon = match line.iter().position(|&b| b == b':') {
+                    Some(colon) => colon,
+                    None => continue,
+                };
+                let name = &line[..colon];
+                let value = super::trim_ows(&line[colon + 1..]);
+                if name.eq_ignore_ascii_case(b"content-length") {
+                    content_length = std::str::from_utf8(value)
+                        .ok()
+                        .and_then(|len| len.parse().ok())
+                        .unwrap_or(0);
+                } else if name.eq_ignore_ascii_case(b"transfer-encoding") {
+                    chunked = value
+                        .rsplit(|&b| b == b',')
+                        .next()
+                        .map(|coding| super::trim_ows(coding).eq_ignore_ascii_case(b"chunked"))
+                        .unwrap_or(false);
+                } else if name.eq_ignore_ascii_case(b"upgrade") {
+                    self.upgrade = true;
+                }
+                names.push(name);
+            }
+
+            for (name, value) in self.headers.iter() {
+                let present = names
+                    .iter()
+                    .any(|other| other.eq_ignore_ascii_case(name.as_str().as_bytes()));
+                if !present {
+                    self.out.extend_from_slice(name.as_str().as_bytes());
+                    self.out.extend_from_slice(b": ");
+                    self.out.extend_from_slice(value.as_bytes());
+                    self.out.extend_from_slice(b"\r\n");
+                }
+            }
+            self.out.extend_from_slice(rest);
+
+            if chunked {
+                self.state = State::ChunkSize;
+            } else if content_length > 0 {
+                self.state = State::Body(content_length);
+            } else {
+                self.finish_body();
+            }
+        }
+
+        fn finish_body(&mut self) {
+            self.state = if self.upgrade { State::Raw } else { State::Head };
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> ProxyHeaders<T> {
+        fn poll_drain(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            while self.written < self.out.len() {
+                let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.out[self.written..]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                self.written += n;
+            }
+            self.out.clear();
+            self.written = 0;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// The size of a chunk from its size line.
+    fn chunk_size(line: &[u8]) -> Option<u64> {
+        let size = line
+            .split(|&b| b == b';' || b == b'\r' || b == b'\n')
+            .next()
+            .unwrap_or(&[]);
+        let size = std::str::from_utf8(super::trim_ows(size)).ok()?;
+        u64::from_str_radix(size, 16).ok()
+    }
+
+    impl<T: Connection> Connection for ProxyHeaders<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected()
+        }
+    }
+
+    impl<T: AsyncRead + Unpin> AsyncRead for ProxyHeaders<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8]
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+
+        unsafe fn prepare_uninitialized_buffer(
+            &self,
+            buf: &mut [MaybeUninit<u8>]
+        ) -> bool {
+            self.inner.prepare_uninitialized_buffer(buf)
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> AsyncWrite for ProxyHeaders<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8]
+        ) -> Poll<io::Result<usize>> {
+            let this = &mut *self;
+            ready!(this.poll_drain(cx))?;
+            match this.state {
+                State::Raw => return Pin::new(&mut this.inner).poll_write(cx, buf),
+                State::Body(remaining) | State::ChunkData(remaining) => {
+                    let len = cmp::min(remaining, buf.len() as u64) as usize;
+                    let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..len]))?;
+                    let left = remaining - n as u64;
+                    match this.state {
+                        State::Body(_) if left == 0 => this.finish_body(),
+                        State::Body(_) => this.state = State::Body(left),
+                        _ if left == 0 => this.state = State::ChunkEnd,
+                        _ => this.state = State::ChunkData(left),
+                    }
+                    return Poll::Ready(Ok(n));
+                }
+                _ => (),
+            }
+
+            let mut read = 0;
+            while read < buf.len() && this.is_framing() {
+                this.push(buf[read]);
+                read += 1;
+            }
+            // What's buffered goes out with the next write or flush if the
+            // connection isn't ready for it.
+            if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+                return Poll::Ready(Err(err));
+            }
+            Poll::Ready(Ok(read))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            ready!(self.poll_drain(cx))?;
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context
+        ) -> Poll<io::Result<()>> {
+            ready!(self.poll_drain(cx))?;
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
 }
 
 mod verbose {
@@ -1068,6 +2434,7 @@ mod verbose {
 mod tests {
     use super::tunnel;
     use crate::proxy;
+    use http::header::{HeaderMap, HeaderValue};
     use std::io::{Read, Write};
     use std::net::TcpListener;
     use std::thread;
@@ -1130,7 +2497,7 @@ mod tests {
             let tcp = TcpStream::connect(&addr).await?;
             let host = addr.ip().to_string();
             let port = addr.port();
-            tunnel(tcp, host, port, ua(), None).await
+            tunnel(tcp, host, port, ua(), None, HeaderMap::new(), None).await
         };
 
         rt.block_on(f).unwrap();
@@ -1145,7 +2512,7 @@ mod tests {
             let tcp = TcpStream::connect(&addr).await?;
             let host = addr.ip().to_string();
             let port = addr.port();
-            tunnel(tcp, host, port, ua(), None).await
+            tunnel(tcp, host, port, ua(), None, HeaderMap::new(), None).await
         };
 
         rt.block_on(f).unwrap_err();
@@ -1160,7 +2527,7 @@ mod tests {
             let tcp = TcpStream::connect(&addr).await?;
             let host = addr.ip().to_string();
             let port = addr.port();
-            tunnel(tcp, host, port, ua(), None).await
+            tunnel(tcp, host, port, ua(), None, HeaderMap::new(), None).await
         };
 
         rt.block_on(f).unwrap_err();
@@ -1181,7 +2548,7 @@ mod tests {
             let tcp = TcpStream::connect(&addr).await?;
             let host = addr.ip().to_string();
             let port = addr.port();
-            tunnel(tcp, host, port, ua(), None).await
+            tunnel(tcp, host, port, ua(), None, HeaderMap::new(), None).await
         };
 
         let error = rt.block_on(f).unwrap_err();
@@ -1206,10 +2573,133 @@ mod tests {
                 port,
                 ua(),
                 Some(proxy::encode_basic_auth("Aladdin", "open sesame")),
+                HeaderMap::new(),
+                None,
             )
             .await
         };
 
         rt.block_on(f).unwrap();
     }
+
+    #[test]
+    fn test_tunnel_custom_headers() {
+        let addr = mock_tunnel!(TUNNEL_OK, "x-tenant-id: acme\r\n");
+
+        let mut rt = runtime::Builder::new().basic_scheduler().enable_all().build().expect("new rt");
+        let f = async move {
+            let tcp = TcpStream::connect(&addr).await?;
+            let host = addr.ip().to_string();
+            let port = addr.port();
+            let mut headers = HeaderMap::new();
+            headers.insert("x-tenant-id", HeaderValue::from_static("acme"));
+            tunnel(tcp, host, port, ua(), None, headers, None).await
+        };
+
+        rt.block_on(f).unwrap();
+    }
+
+    #[test]
+    fn test_tunnel_refused_headers() {
+        let addr = mock_tunnel!(
+            b"\
+            HTTP/1.1 403 Forbidden\r\n\
+            X-Denied-Reason:  missing routing header \r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+        "
+        );
+
+        let mut rt = runtime::Builder::new().basic_scheduler().enable_all().build().expect("new rt");
+        let f = async move {
+            let tcp = TcpStream::connect(&addr).await?;
+            let host = addr.ip().to_string();
+            let port = addr.port();
+            tunnel(tcp, host, port, ua(), None, HeaderMap::new(), None).await
+        };
+
+        let error = rt.block_on(f).unwrap_err();
+        assert_eq!(error.to_string(), "unsuccessful tunnel (403 Forbidden)");
+        let refused = error
+            .downcast_ref::<crate::error::TunnelRefused>()
+            .expect("TunnelRefused");
+        assert_eq!(refused.status, http::StatusCode::FORBIDDEN);
+        assert_eq!(refused.headers["x-denied-reason"], "missing routing header");
+        assert_eq!(refused.headers["content-length"], "0");
+    }
+
+    #[cfg(feature = "socks")]
+    #[test]
+    fn test_socks_target_addr() {
+        use super::socks::target_addr;
+
+        let v6 = "[::1]:80".parse().unwrap();
+        let v4 = "127.0.0.1:80".parse().unwrap();
+        assert_eq!(target_addr(vec![v6, v4], false), Some(v6));
+        assert_eq!(target_addr(vec![v6, v4], true), Some(v4));
+        assert_eq!(target_addr(vec![v6], true), Some(v6));
+        assert_eq!(target_addr(vec![], true), None);
+    }
+
+    #[cfg(feature = "socks")]
+    #[test]
+    fn test_socks4_reply() {
+        fn mock_socks4(reply: &'static [u8]) -> std::net::SocketAddr {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            thread::spawn(move || {
+                let (mut sock, _) = listener.accept().unwrap();
+                // A CONNECT to 127.0.0.1:80 with an empty user ID.
+                let mut request = [0u8; 9];
+                sock.read_exact(&mut request).unwrap();
+                assert_eq!(request, [4, 1, 0, 80, 127, 0, 0, 1, 0]);
+                sock.write_all(reply).unwrap();
+            });
+            addr
+        }
+
+        let mut rt = runtime::Builder::new().basic_scheduler().enable_all().build().expect("new rt");
+        let granted = mock_socks4(&[0, 90, 0, 80, 127, 0, 0, 1]);
+        rt.block_on(super::socks::socks4(granted, "127.0.0.1", 80, ""))
+            .unwrap();
+        let invalid = mock_socks4(&[4, 90, 0, 80, 127, 0, 0, 1]);
+        rt.block_on(super::socks::socks4(invalid, "127.0.0.1", 80, ""))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_proxy_headers() {
+        use super::ProxyHeaders;
+        use tokio::io::AsyncWriteExt;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("proxy-authorization", HeaderValue::from_static("Basic YTpi"));
+        headers.insert("x-tenant-id", HeaderValue::from_static("acme"));
+        let mut conn = ProxyHeaders::new(Vec::new(), headers);
+
+        let mut rt = runtime::Builder::new().basic_scheduler().build().expect("new rt");
+        rt.block_on(async {
+            // Split writes, a chunked body with trailers and a request that
+            // already has one of the headers.
+            conn.write_all(b"POST http://hyper.rs/ HTTP/1.1\r\nhost: hyper.rs\r\n").await?;
+            conn.write_all(b"transfer-encoding: chunked\r\n\r\n5\r\nhello\r\n").await?;
+            conn.write_all(b"0\r\nx-trailer: 1\r\n\r\n").await?;
+            conn.write_all(b"GET http://hyper.rs/ HTTP/1.1\r\nX-Tenant-Id: other\r\n\r\n").await?;
+            conn.flush().await
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(conn.get_ref()),
+            "POST http://hyper.rs/ HTTP/1.1\r\n\
+             proxy-authorization: Basic YTpi\r\n\
+             x-tenant-id: acme\r\n\
+             host: hyper.rs\r\n\
+             transfer-encoding: chunked\r\n\r\n\
+             5\r\nhello\r\n0\r\nx-trailer: 1\r\n\r\n\
+             GET http://hyper.rs/ HTTP/1.1\r\n\
+             proxy-authorization: Basic YTpi\r\n\
+             X-Tenant-Id: other\r\n\r\n"
+        );
+    }
 }
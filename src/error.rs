@@ -3,6 +3,7 @@ use std::error::Error as StdError;
 use std::fmt;
 use std::io;
 
+use crate::header::HeaderMap;
 use crate::{StatusCode, Url};
 
 /// A `Result` alias where the `Err` case is `reqwest::Error`.
@@ -120,6 +121,58 @@ impl Error {
         false
     }
 
+    /// Returns true if the error is from a server certificate that did not
+    /// match the pins set with `ClientBuilder::pin_certificate`.
+    ///
+    /// These errors are also connect errors.
+    pub fn is_certificate_pin_mismatch(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<PinMismatch>() {
+                return true;
+            }
+            source = err.source();
+        }
+
+        false
+    }
+
+    /// Returns true if the error is from a server certificate that was
+    /// revoked, either by a list added with `ClientBuilder::add_crl` or by
+    /// the OCSP response stapled to a rustls handshake.
+    ///
+    /// These errors are also connect errors.
+    pub fn is_certificate_revoked(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<CertificateRevoked>() {
+                return true;
+            }
+            source = err.source();
+        }
+
+        false
+    }
+
+    /// Returns the status and headers of a proxy's response, if the error is
+    /// from the proxy refusing to tunnel to the server with `CONNECT`.
+    ///
+    /// These errors are also connect errors.
+    pub fn proxy_connect_response(&self) -> Option<(StatusCode, &HeaderMap)> {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if let Some(refused) = err.downcast_ref::<TunnelRefused>() {
+                return Some((refused.status, &refused.headers));
+            }
+            source = err.source();
+        }
+
+        None
+    }
+
     /// Returns true if the error is related to the request or response body
     pub fn is_body(&self) -> bool {
         match self.inner.kind {
@@ -313,6 +366,50 @@ impl fmt::Display for TimedOut {
 
 impl StdError for TimedOut {}
 
+#[derive(Debug)]
+pub(crate) struct PinMismatch {
+    pub(crate) host: String,
+}
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "certificate of {} does not match any pin", self.host)
+    }
+}
+
+impl StdError for PinMismatch {}
+
+#[derive(Debug)]
+pub(crate) struct TunnelRefused {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+}
+
+impl fmt::Display for TunnelRefused {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.status == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+            f.write_str("proxy authentication required")
+        } else {
+            write!(f, "unsuccessful tunnel ({})", self.status)
+        }
+    }
+}
+
+impl StdError for TunnelRefused {}
+
+#[derive(Debug)]
+pub(crate) struct CertificateRevoked {
+    pub(crate) host: String,
+}
+
+impl fmt::Display for CertificateRevoked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "certificate of {} has been revoked", self.host)
+    }
+}
+
+impl StdError for CertificateRevoked {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,4 +467,22 @@ mod tests {
         let nested = super::request(io);
         assert!(nested.is_timeout());
     }
+
+    #[test]
+    fn is_certificate_pin_mismatch() {
+        let err = super::request(super::PinMismatch {
+            host: "example.com".into(),
+        });
+        assert!(err.is_certificate_pin_mismatch());
+        assert!(!err.is_timeout());
+    }
+
+    #[test]
+    fn is_certificate_revoked() {
+        let err = super::request(super::CertificateRevoked {
+            host: "example.com".into(),
+        });
+        assert!(err.is_certificate_revoked());
+        assert!(!err.is_certificate_pin_mismatch());
+    }
 }
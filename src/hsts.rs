@@ -0,0 +1,412 @@
+//! HTTP Strict Transport Security
+//!
+//! A `Client` with an HSTS [`Store`] remembers hosts that sent a
+//! `Strict-Transport-Security` header over HTTPS, and rewrites later `http://`
+//! requests to them, including redirect targets, to `https://`.
+//!
+//! Enable it with `ClientBuilder::hsts`, or share a preloaded or persisted
+//! store with `ClientBuilder::hsts_store`.
+//!
+//! # Example
+//!
+//! ```rust
+//! # fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::sync::Arc;
+//! use reqwest::hsts;
+//!
+//! let store = Arc::new(hsts::Store::new());
+//! store.preload("example.com", true);
+//!
+//! let client = reqwest::Client::builder()
+//!     .hsts_store(store.clone())
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Store`]: struct.Store.html
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::net::IpAddr;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::header::{HeaderMap, STRICT_TRANSPORT_SECURITY};
+use crate::Url;
+
+/// A store of known HSTS hosts.
+///
+/// The store can be shared between clients, and saved and loaded to keep
+/// the known hosts across runs.
+#[derive(Debug, Default)]
+pub struct Store {
+    hosts: RwLock<HashMap<String, Entry>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    include_subdomains: bool,
+    // `None` for preloaded hosts, which never expire.
+    expires: Option<SystemTime>,
+}
+
+impl Store {
+    /// Create an empty `Store`.
+    pub fn new() -> Store {
+        Store::default()
+    }
+
+    /// Add a host that should always be reached over HTTPS.
+    ///
+    /// Preloaded hosts never expire, and `Strict-Transport-Security` headers
+    /// received from the host, including `max-age=0`, don't change them.
+    pub fn preload(&self, host: &str, include_subdomains: bool) {
+        if let Some(host) = normalize(host) {
+            self.hosts.write().unwrap().insert(
+                host,
+                Entry {
+                    include_subdomains,
+                    expires: None,
+                },
+            );
+        }
+    }
+
+    /// Returns true if requests to `host` should be made over HTTPS.
+    pub fn is_secure(&self, host: &str) -> bool {
+        let host = match normalize(host) {
+            Some(host) => host,
+            None => return false,
+        };
+        let now = SystemTime::now();
+        let hosts = self.hosts.read().unwrap();
+
+        let mut domain = host.as_str();
+        let mut exact = true;
+        loop {
+            if let Some(entry) = hosts.get(domain) {
+                if !entry.is_expired(now) && (exact || entry.include_subdomains) {
+                    return true;
+                }
+            }
+            match domain.find('.') {
+                Some(idx) => {
+                    domain = &domain[idx + 1..];
+                    exact = false;
+                }
+                None => return false,
+            }
+        }
+    }
+
+    /// Load a `Store` that was written with `Store::save`.
+    ///
+    /// Expired entries are skipped.
+    pub fn load<R: BufRead>(reader: R) -> io::Result<Store> {
+        let store = Store::new();
+        let now = SystemTime::now();
+        {
+            let mut hosts = store.hosts.write().unwrap();
+            for line in reader.lines() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (host, entry) = parse_line(line).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid HSTS store entry: {:?}", line),
+                    )
+                })?;
+                if !entry.is_expired(now) {
+                    hosts.insert(host, entry);
+                }
+            }
+        }
+        Ok(store)
+    }
+
+    /// Write the entries of this `Store`, so they can be read with
+    /// `Store::load`.
+    ///
+    /// Each line holds a host, `1` or `0` for whether subdomains are
+    /// included, and the expiry time in seconds since the Unix epoch (or
+    /// `never` for preloaded hosts).
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let hosts = self.hosts.read().unwrap();
+        let mut sorted = hosts.iter().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.0.cmp(b.0));
+        for (host, entry) in sorted {
+            let expires = match entry.expires {
+                Some(expires) => expires
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    .to_string(),
+                None => "never".to_owned(),
+            };
+            writeln!(
+                writer,
+                "{} {} {}",
+                host,
+                entry.include_subdomains as u8,
+                expires
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Record the `Strict-Transport-Security` header of a response.
+    ///
+    /// Per RFC 6797, the header is ignored unless it was received over
+    /// HTTPS for a domain name.
+    pub(crate) fn store_response(&self, url: &Url, headers: &HeaderMap) {
+        if url.scheme() != "https" {
+            return;
+        }
+        let host = match url.host_str().and_then(normalize) {
+            Some(host) => host,
+            None => return,
+        };
+        let policy = match headers
+            .get(STRICT_TRANSPORT_SECURITY)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_header)
+        {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let mut hosts = self.hosts.write().unwrap();
+        if hosts.get(&host).map(|entry| entry.expires.is_none()) == Some(true) {
+            return;
+        }
+        if policy.max_age == 0 {
+            hosts.remove(&host);
+        } else {
+            let expires = SystemTime::now()
+                .checked_add(Duration::from_secs(policy.max_age))
+                .unwrap_or_else(|| UNIX_EPOCH + Duration::from_secs(u32::MAX as u64));
+            hosts.insert(
+                host,
+                Entry {
+                    include_subdomains: policy.include_subdomains,
+                    expires: Some(expires),
+                },
+            );
+        }
+    }
+
+    /// Rewrite `url` to `https` if its host is known.
+    ///
+    /// Returns true if the `url` was changed.
+    pub(crate) fn upgrade(&self, url: &mut Url) -> bool {
+        if url.scheme() != "http" {
+            return false;
+        }
+        match url.host_str() {
+            Some(host) if self.is_secure(host) => (),
+            _ => return false,
+        }
+        // An explicit port 80 has no meaning over TLS, use the default.
+        if url.port() == Some(80) {
+            let _ = url.set_port(None);
+        }
+        url.set_scheme("https").is_ok()
+    }
+}
+
+impl Entry {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+}
+
+struct Policy {
+    max_age: u64,
+    include_subdomains: bool,
+}
+
+/// Normalizes a host name, rejecting IP addresses, which HSTS ignores.
+fn normalize(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() || bare.parse::<IpAddr>().is_ok() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+fn parse_header(value: &str) -> Option<Policy> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+
+    for directive in value.split(';') {
+        let directive = directive.trim();
+        if directive.is_empty() {
+            continue;
+        }
+        let mut parts = directive.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().map(|v| v.trim().trim_matches('"'));
+
+        if name.eq_ignore_ascii_case("max-age") {
+            // Directives must not appear more than once.
+            if max_age.is_some() {
+                return None;
+            }
+            max_age = Some(value?.parse::<u64>().ok()?);
+        } else if name.eq_ignore_ascii_case("includeSubDomains") {
+            include_subdomains = true;
+        }
+    }
+
+    Some(Policy {
+        max_age: max_age?,
+        include_subdomains,
+    })
+}
+
+fn parse_line(line: &str) -> Option<(String, Entry)> {
+    let mut parts = line.split_whitespace();
+    let host = normalize(parts.next()?)?;
+    let include_subdomains = match parts.next()? {
+        "1" => true,
+        "0" => false,
+        _ => return None,
+    };
+    let expires = match parts.next()? {
+        "never" => None,
+        secs => Some(UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?)),
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((
+        host,
+        Entry {
+            include_subdomains,
+            expires,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderValue;
+
+    fn sts(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn store_response_over_https_only() {
+        let store = Store::new();
+        let headers = sts("max-age=3600");
+
+        store.store_response(&Url::parse("http://example.com/").unwrap(), &headers);
+        assert!(!store.is_secure("example.com"));
+
+        store.store_response(&Url::parse("https://example.com/").unwrap(), &headers);
+        assert!(store.is_secure("example.com"));
+        assert!(store.is_secure("EXAMPLE.com."));
+        assert!(!store.is_secure("www.example.com"));
+    }
+
+    #[test]
+    fn include_subdomains_and_removal() {
+        let store = Store::new();
+        let url = Url::parse("https://example.com/").unwrap();
+
+        store.store_response(&url, &sts("max-age=\"3600\"; includeSubDomains"));
+        assert!(store.is_secure("a.b.example.com"));
+        assert!(!store.is_secure("notexample.com"));
+
+        store.store_response(&url, &sts("max-age=0"));
+        assert!(!store.is_secure("example.com"));
+        assert!(!store.is_secure("a.b.example.com"));
+    }
+
+    #[test]
+    fn preloaded_hosts_are_kept() {
+        let store = Store::new();
+        store.preload("example.com", true);
+        let url = Url::parse("https://example.com/").unwrap();
+
+        store.store_response(&url, &sts("max-age=0"));
+        assert!(store.is_secure("example.com"));
+
+        store.store_response(&url, &sts("max-age=1"));
+        assert!(store.is_secure("sub.example.com"));
+        assert_eq!(
+            store.hosts.read().unwrap()["example.com"].expires,
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_headers_are_ignored() {
+        let store = Store::new();
+        let url = Url::parse("https://example.com/").unwrap();
+
+        store.store_response(&url, &sts("includeSubDomains"));
+        store.store_response(&url, &sts("max-age=abc"));
+        store.store_response(&url, &sts("max-age=1; max-age=2"));
+        assert!(!store.is_secure("example.com"));
+
+        let ip = Url::parse("https://127.0.0.1/").unwrap();
+        store.store_response(&ip, &sts("max-age=3600"));
+        assert!(!store.is_secure("127.0.0.1"));
+    }
+
+    #[test]
+    fn upgrade_url() {
+        let store = Store::new();
+        store.preload("example.com", false);
+
+        let mut url = Url::parse("http://example.com:80/path?q=1").unwrap();
+        assert!(store.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://example.com/path?q=1");
+
+        let mut url = Url::parse("http://example.com:8080/").unwrap();
+        assert!(store.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://example.com:8080/");
+
+        let mut url = Url::parse("http://other.com/").unwrap();
+        assert!(!store.upgrade(&mut url));
+        assert_eq!(url.as_str(), "http://other.com/");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let store = Store::new();
+        store.preload("preloaded.com", true);
+        store.store_response(
+            &Url::parse("https://example.com/").unwrap(),
+            &sts("max-age=3600"),
+        );
+
+        let mut buf = Vec::new();
+        store.save(&mut buf).unwrap();
+
+        let loaded = Store::load(&buf[..]).unwrap();
+        assert!(loaded.is_secure("sub.preloaded.com"));
+        assert!(loaded.is_secure("example.com"));
+        assert!(!loaded.is_secure("sub.example.com"));
+
+        let expired = b"# comment\nold.com 0 1\n";
+        let loaded = Store::load(&expired[..]).unwrap();
+        assert!(!loaded.is_secure("old.com"));
+
+        assert!(Store::load(&b"bad.com maybe never\n"[..]).is_err());
+    }
+}
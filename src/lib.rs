@@ -141,6 +141,9 @@
 //! i.e. `let proxy = reqwest::Proxy::http("https://secure.example")?;`
 //! or disabled by calling `ClientBuilder::no_proxy()`.
 //!
+//! `NO_PROXY` or `no_proxy` list hosts that bypass system proxies. The same
+//! rules can be given to any `Proxy` with a [`NoProxy`](NoProxy).
+//!
 //! `socks` feature is required if you have configured socks proxy like this:
 //!
 //! ```bash
@@ -159,6 +162,8 @@
 //!   [`Identity`][Identity] type.
 //! - Various parts of TLS can also be configured or even disabled on the
 //!   `ClientBuilder`.
+//! - HTTPS proxies can be given their own trust roots and client certificate
+//!   with [`tls::ProxyTlsConfig`](tls::ProxyTlsConfig).
 //!
 //! ## Optional Features
 //!
@@ -170,15 +175,22 @@
 //! - **native-tls**: Enables TLS functionality provided by `native-tls`.
 //! - **native-tls-vendored**: Enables the `vendored` feature of `native-tls`.
 //! - **rustls-tls**: Enables TLS functionality provided by `rustls`.
+//! - **rustls-tls-pkcs12**: Enables PKCS #12 archives and encrypted private
+//!   keys as client identities with `rustls`.
 //! - **blocking**: Provides the [blocking][] client API.
 //! - **cookies**: Provides cookie session support.
 //! - **gzip**: Provides response body gzip decompression.
 //! - **brotli**: Provides response body brotli decompression.
 //! - **json**: Provides serialization and deserialization for JSON bodies.
 //! - **stream**: Adds support for `futures::Stream`.
-//! - **socks**: Provides SOCKS5 proxy support.
+//! - **socks**: Provides SOCKS4 and SOCKS5 proxy support.
+//! - **pac**: Provides proxy auto-config scripts with `Proxy::pac`.
+//! - **negotiate**: Provides NTLM authentication with servers and proxies.
+//! - **aws-sigv4**: Provides signing requests with AWS Signature Version 4.
 //! - **trust-dns**: Enables a trust-dns async resolver instead of default
 //!   threadpool using `getaddrinfo`.
+//! - **psl**: Enables trusting redirects within the same registrable domain,
+//!   using the Public Suffix List.
 //!
 //!
 //! [hyper]: http://hyper.rs
@@ -214,9 +226,11 @@ pub use url::Url;
 #[macro_use]
 mod error;
 mod into_url;
+mod response;
 
 pub use self::error::{Error, Result};
 pub use self::into_url::IntoUrl;
+pub use self::response::ResponseBuilderExt;
 
 /// Shortcut method to quickly make a `GET` request.
 ///
@@ -282,14 +296,15 @@ if_hyper! {
     doctest!("../README.md");
 
     pub use self::async_impl::{
-        multipart, Body, Client, ClientBuilder, Request, RequestBuilder, Response, ResponseBuilderExt,
+        multipart, Body, Client, ClientBuilder, Request, RequestBuilder, Response,
     };
-    pub use self::proxy::Proxy;
+    pub use self::proxy::{NoProxy, Proxy};
     #[cfg(feature = "__tls")]
     pub use self::tls::{Certificate, Identity};
 
 
     mod async_impl;
+    pub mod auth;
     #[cfg(feature = "blocking")]
     pub mod blocking;
     mod connect;
@@ -297,10 +312,12 @@ if_hyper! {
     pub mod cookie;
     #[cfg(feature = "trust-dns")]
     mod dns;
+    pub mod hsts;
     mod proxy;
     pub mod redirect;
+    pub mod sign;
     #[cfg(feature = "__tls")]
-    mod tls;
+    pub mod tls;
     mod util;
 }
 
@@ -0,0 +1,430 @@
+//! Proxy auto-config (PAC) scripts.
+//!
+//! Scripts run in QuickJS, with a fresh runtime for each evaluation. The
+//! helper functions of the original Netscape specification are JavaScript
+//! in `UTILS`, apart from the ones that need the network, which are Rust.
+//! Those block on DNS, so scripts are evaluated on the blocking thread pool
+//! when connecting, see `Proxy::may_block`.
+
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+
+use rquickjs::convert::Coerced;
+use rquickjs::{CatchResultExt, Context, Ctx, Function, Runtime};
+
+use super::ProxyScheme;
+use crate::Url;
+
+/// How often the engine may check in during an evaluation, which it does
+/// about every 10 000 jumps and calls. This stops endless loops.
+const MAX_INTERRUPTS: u32 = 1_000;
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+const STACK_SIZE: usize = 256 * 1024;
+
+/// The helpers that don't need the network. Times are local, unless the
+/// last argument is `"GMT"`.
+const UTILS: &str = r#"
+function isPlainHostName(host) {
+    return host.indexOf(".") == -1;
+}
+
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length &&
+        host.substring(host.length - domain.length) == domain;
+}
+
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + ".", 0) == 0;
+}
+
+function isResolvable(host) {
+    return dnsResolve(host) != null;
+}
+
+function convert_addr(ipchars) {
+    var bytes = ipchars.split(".");
+    return ((bytes[0] & 0xff) << 24 | (bytes[1] & 0xff) << 16 |
+        (bytes[2] & 0xff) << 8 | (bytes[3] & 0xff)) >>> 0;
+}
+
+function isInNet(host, pattern, mask) {
+    var ip = dnsResolve(host);
+    if (ip == null) {
+        return false;
+    }
+    mask = convert_addr(mask);
+    return ((convert_addr(ip) & mask) >>> 0) == ((convert_addr(pattern) & mask) >>> 0);
+}
+
+function dnsDomainLevels(host) {
+    return host.split(".").length - 1;
+}
+
+function shExpMatch(str, shexp) {
+    var pattern = shexp
+        .replace(/[.+^${}()|[\]\\]/g, "\\$&")
+        .replace(/\*/g, ".*")
+        .replace(/\?/g, ".");
+    return new RegExp("^" + pattern + "$").test(str);
+}
+
+function weekdayRange() {
+    var days = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+    var args = Array.prototype.slice.call(arguments);
+    var gmt = args[args.length - 1] == "GMT";
+    if (gmt) {
+        args.pop();
+    }
+    var now = new Date();
+    var today = gmt ? now.getUTCDay() : now.getDay();
+    var start = days.indexOf(args[0]);
+    var end = args.length > 1 ? days.indexOf(args[1]) : start;
+    if (start == -1 || end == -1 || args.length > 2) {
+        return false;
+    }
+    return start <= end ? start <= today && today <= end : today >= start || today <= end;
+}
+
+function timeRange() {
+    var args = Array.prototype.slice.call(arguments);
+    var gmt = args[args.length - 1] == "GMT";
+    if (gmt) {
+        args.pop();
+    }
+    var now = new Date();
+    var secs = gmt
+        ? now.getUTCHours() * 3600 + now.getUTCMinutes() * 60 + now.getUTCSeconds()
+        : now.getHours() * 3600 + now.getMinutes() * 60 + now.getSeconds();
+    var value, start, end;
+    switch (args.length) {
+        case 1:
+            return Math.floor(secs / 3600) == args[0];
+        case 2:
+            // The end hour is exclusive.
+            value = Math.floor(secs / 3600);
+            start = +args[0];
+            end = (+args[1] + 23) % 24;
+            break;
+        case 4:
+            // So is the end minute.
+            value = Math.floor(secs / 60);
+            start = args[0] * 60 + +args[1];
+            end = (args[2] * 60 + +args[3] + 1439) % 1440;
+            break;
+        case 6:
+            value = secs;
+            start = args[0] * 3600 + args[1] * 60 + +args[2];
+            end = args[3] * 3600 + args[4] * 60 + +args[5];
+            break;
+        default:
+            return false;
+    }
+    return start <= end ? start <= value && value <= end : value >= start || value <= end;
+}
+"#;
+
+/// A PAC script.
+pub(crate) struct Pac {
+    source: String,
+}
+
+impl Pac {
+    /// Checks that `source` runs, and declares `FindProxyForURL`.
+    pub(crate) fn parse(source: &str) -> Result<Pac, String> {
+        let pac = Pac {
+            source: source.to_owned(),
+        };
+        let declared = pac.with_script(|ctx| {
+            ctx.globals()
+                .get::<_, Option<Function>>("FindProxyForURL")
+                .map(|find| find.is_some())
+                .map_err(|err| err.to_string())
+        })?;
+        if !declared {
+            return Err("PAC script doesn't declare FindProxyForURL".into());
+        }
+        Ok(pac)
+    }
+
+    /// The proxies to try for `url` in order, where `None` means connecting
+    /// directly. This may block on DNS.
+    ///
+    /// A script that fails, or returns no usable entry, means `DIRECT`.
+    pub(crate) fn find_proxy(&self, url: &Url) -> Vec<Option<ProxyScheme>> {
+        let host = url.host_str().unwrap_or("");
+        match self.find_proxy_for_url(url.as_str(), host) {
+            Ok(result) => parse_result(&result),
+            Err(err) => {
+                log::warn!("PAC script failed for {}: {}", url, err);
+                vec![None]
+            }
+        }
+    }
+
+    fn find_proxy_for_url(&self, url: &str, host: &str) -> Result<String, String> {
+        self.with_script(|ctx| {
+            let find: Function = ctx
+                .globals()
+                .get("FindProxyForURL")
+                .catch(&ctx)
+                .map_err(|err| err.to_string())?;
+            let result: Coerced<String> = find
+                .call((url, host))
+                .catch(&ctx)
+                .map_err(|err| err.to_string())?;
+            Ok(result.0)
+        })
+    }
+
+    /// Runs the script in a fresh runtime with the helpers, and then `f`.
+    fn with_script<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(Ctx) -> Result<T, String>,
+    {
+        let runtime = Runtime::new().map_err(|err| err.to_string())?;
+        runtime.set_memory_limit(MEMORY_LIMIT);
+        runtime.set_max_stack_size(STACK_SIZE);
+        let mut interrupts = 0;
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            interrupts += 1;
+            interrupts > MAX_INTERRUPTS
+        })));
+        let context = Context::full(&runtime).map_err(|err| err.to_string())?;
+
+        context.with(|ctx| {
+            add_network_helpers(&ctx).map_err(|err| err.to_string())?;
+            ctx.eval::<(), _>(UTILS)
+                .catch(&ctx)
+                .map_err(|err| err.to_string())?;
+            ctx.eval::<(), _>(&self.source[..])
+                .catch(&ctx)
+                .map_err(|err| err.to_string())?;
+            f(ctx)
+        })
+    }
+}
+
+/// Adds `dnsResolve`, `myIpAddress` and `alert`.
+fn add_network_helpers(ctx: &Ctx) -> rquickjs::Result<()> {
+    let globals = ctx.globals();
+    let resolve = Function::new(ctx.clone(), |host: Coerced<String>| {
+        resolve(&host.0).map(|ip| ip.to_string())
+    })?;
+    globals.set("__dnsResolve", resolve)?;
+    ctx.eval::<(), _>(
+        "function dnsResolve(host) { var ip = __dnsResolve(host); return ip === undefined ? null : ip; }",
+    )?;
+    globals.set("myIpAddress", Function::new(ctx.clone(), my_ip_address)?)?;
+    let alert = Function::new(ctx.clone(), |message: Coerced<String>| {
+        log::debug!("PAC alert: {}", message.0);
+    })?;
+    globals.set("alert", alert)?;
+    Ok(())
+}
+
+/// Parses a `FindProxyForURL` result like `PROXY a:8080; SOCKS b:1080; DIRECT`.
+fn parse_result(result: &str) -> Vec<Option<ProxyScheme>> {
+    let mut proxies = Vec::new();
+    for entry in result.split(';').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+        let mut parts = entry.split_whitespace();
+        let kind = parts.next().unwrap_or("").to_ascii_uppercase();
+        let scheme = match (kind.as_str(), parts.next()) {
+            ("DIRECT", None) => {
+                proxies.push(None);
+                continue;
+            }
+            ("PROXY", Some(addr)) | ("HTTP", Some(addr)) => format!("http://{}", addr),
+            ("HTTPS", Some(addr)) => format!("https://{}", addr),
+            #[cfg(feature = "socks")]
+            ("SOCKS", Some(addr)) | ("SOCKS5", Some(addr)) => format!("socks5://{}", addr),
+            #[cfg(feature = "socks")]
+            ("SOCKS4", Some(addr)) => format!("socks4://{}", addr),
+            _ => {
+                log::debug!("ignoring unsupported PAC entry {:?}", entry);
+                continue;
+            }
+        };
+        match Url::parse(&scheme)
+            .map_err(crate::error::builder)
+            .and_then(ProxyScheme::parse)
+        {
+            Ok(scheme) => proxies.push(Some(scheme)),
+            Err(err) => log::debug!("ignoring PAC entry {:?}: {}", entry, err),
+        }
+    }
+    if proxies.is_empty() {
+        proxies.push(None);
+    }
+    proxies
+}
+
+/// Resolves `host` to an IPv4 address. This blocks on DNS.
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    if let Ok(ip) = host.parse() {
+        return Some(ip);
+    }
+    (host, 0).to_socket_addrs().ok()?.find_map(|addr| match addr.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    })
+}
+
+/// The address of the interface that routes to the internet. Connecting a
+/// UDP socket sends nothing, it only picks the route.
+fn my_ip_address() -> String {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("198.51.100.1:53")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        // Proxies for the office.
+        var proxies = ["PROXY p1.example:8080", "PROXY p2.example:8080"];
+
+        function isInternal(host) {
+            var suffixes = [".corp.example", ".lan"];
+            for (var i = 0; i < suffixes.length; i++) {
+                if (dnsDomainIs(host, suffixes[i])) return true;
+            }
+            return false;
+        }
+
+        function FindProxyForURL(url, host) {
+            host = host.toLowerCase();
+            if (isPlainHostName(host) || isInternal(host))
+                return "DIRECT";
+            if (isInNet(host, "10.0.0.0", "255.0.0.0"))
+                return 'DIRECT';
+            if (shExpMatch(url, "https://*.secure.example/*"))
+                return "HTTPS tls-proxy.example:8443";
+            if (/^http:/.test(url) && host.indexOf("legacy") != -1) {
+                return "QUIC old.example:443; " + proxies[1];
+            }
+            return proxies.join("; ") + "; DIRECT";
+        }
+    "#;
+
+    fn eval(script: &str, url: &str, host: &str) -> String {
+        Pac::parse(script)
+            .unwrap()
+            .find_proxy_for_url(url, host)
+            .unwrap()
+    }
+
+    fn find_proxy(script: &str, url: &str) -> Vec<Option<String>> {
+        Pac::parse(script)
+            .unwrap()
+            .find_proxy(&Url::parse(url).unwrap())
+            .into_iter()
+            .map(|proxy| proxy.map(|proxy| format!("{}://{}", proxy.scheme(), proxy.host())))
+            .collect()
+    }
+
+    /// The value of `expr` in `FindProxyForURL`.
+    fn call(expr: &str) -> String {
+        let script = format!("function FindProxyForURL(url, host) {{ return {}; }}", expr);
+        eval(&script, "http://hyper.rs/", "hyper.rs")
+    }
+
+    #[test]
+    fn find_proxy_for_url() {
+        assert_eq!(eval(SCRIPT, "http://intranet/", "intranet"), "DIRECT");
+        assert_eq!(eval(SCRIPT, "http://wiki.corp.example/", "WIKI.corp.example"), "DIRECT");
+        assert_eq!(eval(SCRIPT, "http://10.1.2.3/", "10.1.2.3"), "DIRECT");
+        assert_eq!(
+            eval(SCRIPT, "https://api.secure.example/", "api.secure.example"),
+            "HTTPS tls-proxy.example:8443"
+        );
+        assert_eq!(
+            eval(SCRIPT, "http://hyper.rs/", "hyper.rs"),
+            "PROXY p1.example:8080; PROXY p2.example:8080; DIRECT"
+        );
+    }
+
+    #[test]
+    fn ordered_proxies() {
+        assert_eq!(
+            find_proxy(SCRIPT, "http://hyper.rs/"),
+            vec![
+                Some("http://p1.example:8080".into()),
+                Some("http://p2.example:8080".into()),
+                None,
+            ]
+        );
+        assert_eq!(
+            find_proxy(SCRIPT, "https://api.secure.example/"),
+            vec![Some("https://tls-proxy.example:8443".into())]
+        );
+        // Unsupported entries are skipped.
+        assert_eq!(
+            find_proxy(SCRIPT, "http://legacy.example/"),
+            vec![Some("http://p2.example:8080".into())]
+        );
+    }
+
+    #[test]
+    fn failures_go_direct() {
+        let script = "function FindProxyForURL(url, host) { return notDefined(host); }";
+        assert_eq!(find_proxy(script, "http://hyper.rs/"), vec![None]);
+
+        let script = "function FindProxyForURL(url, host) { while (true) {} }";
+        assert_eq!(find_proxy(script, "http://hyper.rs/"), vec![None]);
+
+        let script = "function FindProxyForURL(url, host) { return FindProxyForURL(url, host); }";
+        assert_eq!(find_proxy(script, "http://hyper.rs/"), vec![None]);
+
+        let script = "function FindProxyForURL(url, host) { return 'NONSENSE'; }";
+        assert_eq!(find_proxy(script, "http://hyper.rs/"), vec![None]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Pac::parse("function FindProxyForURL(url, host) {").is_err());
+        assert!(Pac::parse("function findProxy(url, host) { return 'DIRECT'; }").is_err());
+        assert!(Pac::parse("var s = 'unterminated;").is_err());
+    }
+
+    #[test]
+    fn helpers() {
+        assert_eq!(call("shExpMatch('www.example.com', '*.example.com')"), "true");
+        assert_eq!(call("shExpMatch('abc', 'a?c')"), "true");
+        assert_eq!(call("shExpMatch('a.b.c', '*.*')"), "true");
+        assert_eq!(call("shExpMatch('example.com', '*.example.com')"), "false");
+        assert_eq!(call("shExpMatch('abcd', 'a?c')"), "false");
+        assert_eq!(call("shExpMatch('a+b', 'a+b')"), "true");
+
+        assert_eq!(call("dnsDomainLevels('www.example.com')"), "2");
+        assert_eq!(call("localHostOrDomainIs('www', 'www.example.com')"), "true");
+        assert_eq!(call("localHostOrDomainIs('www.example.com', 'www.example.com')"), "true");
+        assert_eq!(call("localHostOrDomainIs('home', 'www.example.com')"), "false");
+        assert_eq!(call("isInNet('198.95.249.79', '198.95.0.0', '255.255.0.0')"), "true");
+        assert_eq!(call("isInNet('198.96.1.1', '198.95.0.0', '255.255.0.0')"), "false");
+        assert_eq!(call("convert_addr('104.16.41.2')"), "1745889538");
+        assert_eq!(call("dnsResolve('127.0.0.1')"), "127.0.0.1");
+        assert_eq!(call("weekdayRange('SUN', 'SAT')"), "true");
+        assert_eq!(call("timeRange(0, 0, 0, 23, 59, 59)"), "true");
+    }
+
+    #[test]
+    fn local_time() {
+        // Unless the last argument is "GMT", the current hour and day are
+        // the local ones.
+        assert_eq!(call("timeRange(new Date().getHours())"), "true");
+        assert_eq!(call("timeRange(new Date().getUTCHours(), 'GMT')"), "true");
+        assert_eq!(call("weekdayRange(['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'][new Date().getDay()])"), "true");
+        assert_eq!(
+            call("weekdayRange(['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'][new Date().getUTCDay()], 'GMT')"),
+            "true"
+        );
+    }
+}
@@ -6,8 +6,12 @@
 
 use std::error::Error as StdError;
 use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
 
-use crate::header::{HeaderMap, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE};
+use crate::header::{
+    HeaderMap, HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE,
+};
 use hyper::StatusCode;
 
 use crate::Url;
@@ -21,8 +25,41 @@ use crate::Url;
 ///   the allowed maximum redirect hops in a chain.
 /// - `none` can be used to disable all redirect behavior.
 /// - `custom` can be used to create a customized policy.
+/// - `custom_async` can be used to create a customized policy that may do
+///   I/O before deciding.
+///
+/// Which headers are removed when a redirect leaves the original host can be
+/// configured with `sensitive_headers`.
 pub struct Policy {
     inner: PolicyKind,
+    sensitive: SensitiveHeaders,
+}
+
+/// Rules for removing sensitive headers when following a redirect.
+///
+/// By default, the `Authorization`, `Cookie`, `Cookie2`,
+/// `Proxy-Authorization` and `WWW-Authenticate` headers are removed whenever
+/// a redirect changes the host or port.
+///
+/// # Example
+///
+/// ```rust
+/// # use reqwest::redirect;
+/// # use reqwest::header::HeaderName;
+/// let sensitive = redirect::SensitiveHeaders::default()
+///     .header(HeaderName::from_static("x-api-key"))
+///     .trust_subdomains(true)
+///     .strip_on_scheme_downgrade(true);
+/// let policy = redirect::Policy::default().sensitive_headers(sensitive);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SensitiveHeaders {
+    headers: Vec<HeaderName>,
+    trusted_hosts: Vec<String>,
+    trust_subdomains: bool,
+    #[cfg(feature = "psl")]
+    trust_registrable_domain: bool,
+    strip_on_downgrade: bool,
 }
 
 /// A type that holds information on the next request and previous requests
@@ -30,10 +67,36 @@ pub struct Policy {
 #[derive(Debug)]
 pub struct Attempt<'a> {
     status: StatusCode,
+    headers: &'a HeaderMap,
     next: &'a Url,
     previous: &'a [Url],
 }
 
+/// An owned variant of [`Attempt`] that is passed to asynchronous policies.
+///
+/// Besides the information about the redirect, it holds the headers that
+/// will be sent with the next request, which the policy may modify.
+///
+/// [`Attempt`]: struct.Attempt.html
+#[derive(Debug)]
+pub struct AsyncAttempt {
+    status: StatusCode,
+    headers: HeaderMap,
+    next: Url,
+    previous: Vec<Url>,
+    request_headers: HeaderMap,
+}
+
+/// A redirect response that was followed on the way to the final `Response`.
+///
+/// See `Response::redirect_history()`.
+#[derive(Clone, Debug)]
+pub struct Hop {
+    url: Url,
+    status: StatusCode,
+    headers: HeaderMap,
+}
+
 /// An action to perform when a redirect status code is found.
 #[derive(Debug)]
 pub struct Action {
@@ -47,6 +110,7 @@ impl Policy {
     pub fn limited(max: usize) -> Self {
         Self {
             inner: PolicyKind::Limit(max),
+            sensitive: SensitiveHeaders::default(),
         }
     }
 
@@ -54,6 +118,7 @@ impl Policy {
     pub fn none() -> Self {
         Self {
             inner: PolicyKind::None,
+            sensitive: SensitiveHeaders::default(),
         }
     }
 
@@ -101,9 +166,73 @@ impl Policy {
     {
         Self {
             inner: PolicyKind::Custom(Box::new(policy)),
+            sensitive: SensitiveHeaders::default(),
         }
     }
 
+    /// Create a custom `Policy` using the passed asynchronous function.
+    ///
+    /// This works like [`Policy::custom`], but the returned future may do
+    /// I/O, such as consulting an allow-list service, before deciding.
+    ///
+    /// The [`AsyncAttempt`] passed to the function also holds the headers
+    /// of the next request, after sensitive headers have been removed for a
+    /// cross-host redirect. Changes made to them through
+    /// [`AsyncAttempt::request_headers_mut`] are sent if the redirect is
+    /// followed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use reqwest::{Error, redirect};
+    /// # use reqwest::header::{HeaderValue, AUTHORIZATION};
+    /// #
+    /// # fn run() -> Result<(), Error> {
+    /// let custom = redirect::Policy::custom_async(|mut attempt| async move {
+    ///     if attempt.previous().len() > 5 {
+    ///         attempt.error("too many redirects")
+    ///     } else if attempt.url().host_str() == Some("api.example.domain") {
+    ///         // re-add credentials for a trusted host
+    ///         attempt
+    ///             .request_headers_mut()
+    ///             .insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+    ///         attempt.follow()
+    ///     } else {
+    ///         attempt.follow()
+    ///     }
+    /// });
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(custom)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Policy::custom`]: #method.custom
+    /// [`AsyncAttempt`]: struct.AsyncAttempt.html
+    /// [`AsyncAttempt::request_headers_mut`]: struct.AsyncAttempt.html#method.request_headers_mut
+    pub fn custom_async<T, F>(policy: T) -> Self
+    where
+        T: Fn(AsyncAttempt) -> F + Send + Sync + 'static,
+        F: Future<Output = Action> + Send + 'static,
+    {
+        Self {
+            inner: PolicyKind::Async(Box::new(move |attempt| Box::pin(policy(attempt)))),
+            sensitive: SensitiveHeaders::default(),
+        }
+    }
+
+    /// Set the rules for removing sensitive headers when following a
+    /// redirect with this `Policy`.
+    ///
+    /// See [`SensitiveHeaders`] for the default rules.
+    ///
+    /// [`SensitiveHeaders`]: struct.SensitiveHeaders.html
+    pub fn sensitive_headers(mut self, sensitive: SensitiveHeaders) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
     /// Apply this policy to a given [`Attempt`] to produce a [`Action`].
     ///
     /// # Note
@@ -135,21 +264,90 @@ impl Policy {
                 }
             }
             PolicyKind::None => attempt.stop(),
+            PolicyKind::Async(..) => {
+                attempt.error("asynchronous redirect policy can't be applied synchronously")
+            }
+        }
+    }
+
+    /// Apply this policy to a given [`AsyncAttempt`] to produce a [`Action`].
+    ///
+    /// Synchronous policies are evaluated immediately.
+    ///
+    /// # Note
+    ///
+    /// This method can be used together with `Policy::custom_async()`
+    /// to construct one `Policy` that wraps another.
+    ///
+    /// [`AsyncAttempt`]: struct.AsyncAttempt.html
+    /// [`Action`]: struct.Action.html
+    pub fn redirect_async(
+        &self,
+        attempt: AsyncAttempt,
+    ) -> impl Future<Output = Action> + Send + 'static {
+        let fut: BoxFuture = match self.inner {
+            PolicyKind::Async(ref custom) => custom(attempt),
+            _ => {
+                let action = self.redirect(attempt.as_attempt());
+                Box::pin(async move { action })
+            }
+        };
+        fut
+    }
+
+    pub(crate) fn is_async(&self) -> bool {
+        match self.inner {
+            PolicyKind::Async(..) => true,
+            _ => false,
         }
     }
 
-    pub(crate) fn check(&self, status: StatusCode, next: &Url, previous: &[Url]) -> ActionKind {
+    pub(crate) fn check(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        next: &Url,
+        previous: &[Url],
+    ) -> ActionKind {
         self.redirect(Attempt {
             status,
+            headers,
             next,
             previous,
         })
         .inner
     }
 
+    pub(crate) fn check_async(
+        &self,
+        status: StatusCode,
+        headers: HeaderMap,
+        next: Url,
+        previous: Vec<Url>,
+        request_headers: HeaderMap,
+    ) -> Pin<Box<dyn Future<Output = ActionKind> + Send>> {
+        let action = self.redirect_async(AsyncAttempt {
+            status,
+            headers,
+            next,
+            previous,
+            request_headers,
+        });
+        Box::pin(async move { action.await.inner })
+    }
+
+    pub(crate) fn remove_sensitive_headers(
+        &self,
+        headers: &mut HeaderMap,
+        next: &Url,
+        previous: &[Url],
+    ) {
+        self.sensitive.remove(headers, next, previous);
+    }
+
     pub(crate) fn is_default(&self) -> bool {
         match self.inner {
-            PolicyKind::Limit(10) => true,
+            PolicyKind::Limit(10) => self.sensitive == SensitiveHeaders::default(),
             _ => false,
         }
     }
@@ -162,12 +360,152 @@ impl Default for Policy {
     }
 }
 
+impl SensitiveHeaders {
+    /// Create rules that remove no headers at all.
+    ///
+    /// Headers to remove can then be added with `header`.
+    pub fn empty() -> SensitiveHeaders {
+        SensitiveHeaders {
+            headers: Vec::new(),
+            trusted_hosts: Vec::new(),
+            trust_subdomains: false,
+            #[cfg(feature = "psl")]
+            trust_registrable_domain: false,
+            strip_on_downgrade: false,
+        }
+    }
+
+    /// Add a header to be removed when a redirect leaves a trusted host.
+    pub fn header(mut self, name: HeaderName) -> SensitiveHeaders {
+        if !self.headers.contains(&name) {
+            self.headers.push(name);
+        }
+        self
+    }
+
+    /// Stop removing a header, such as one of the defaults.
+    pub fn remove_header(mut self, name: &HeaderName) -> SensitiveHeaders {
+        self.headers.retain(|h| h != name);
+        self
+    }
+
+    /// Trust redirects to this host, keeping sensitive headers.
+    ///
+    /// The host is matched exactly and case-insensitively, on any port.
+    pub fn trust_host<H: Into<String>>(mut self, host: H) -> SensitiveHeaders {
+        self.trusted_hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Trust redirects to subdomains of the previous host.
+    ///
+    /// For example, a redirect from `example.com` to `api.example.com`
+    /// keeps sensitive headers, but one in the other direction doesn't.
+    ///
+    /// Default is `false`.
+    pub fn trust_subdomains(mut self, enable: bool) -> SensitiveHeaders {
+        self.trust_subdomains = enable;
+        self
+    }
+
+    /// Trust redirects to hosts within the same registrable domain as the
+    /// previous host, as determined by the Public Suffix List.
+    ///
+    /// For example, `a.example.co.uk` and `b.example.co.uk` share the
+    /// registrable domain `example.co.uk`.
+    ///
+    /// Default is `false`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `psl` feature to be enabled.
+    #[cfg(feature = "psl")]
+    pub fn trust_same_registrable_domain(mut self, enable: bool) -> SensitiveHeaders {
+        self.trust_registrable_domain = enable;
+        self
+    }
+
+    /// Remove sensitive headers when a redirect goes from `https` to `http`,
+    /// even if the host is the same or trusted.
+    ///
+    /// Default is `false`.
+    pub fn strip_on_scheme_downgrade(mut self, enable: bool) -> SensitiveHeaders {
+        self.strip_on_downgrade = enable;
+        self
+    }
+
+    pub(crate) fn remove(&self, headers: &mut HeaderMap, next: &Url, previous: &[Url]) {
+        if let Some(previous) = previous.last() {
+            if !self.is_trusted(next, previous) {
+                for header in &self.headers {
+                    headers.remove(header);
+                }
+            }
+        }
+    }
+
+    fn is_trusted(&self, next: &Url, previous: &Url) -> bool {
+        if self.strip_on_downgrade && previous.scheme() == "https" && next.scheme() == "http" {
+            return false;
+        }
+
+        let next_host = next.host_str().map(|host| host.to_ascii_lowercase());
+        let previous_host = previous.host_str().map(|host| host.to_ascii_lowercase());
+        let (next_host, previous_host) = match (next_host, previous_host) {
+            (Some(next_host), Some(previous_host)) => (next_host, previous_host),
+            _ => return false,
+        };
+
+        if next_host == previous_host
+            && next.port_or_known_default() == previous.port_or_known_default()
+        {
+            return true;
+        }
+
+        if self.trusted_hosts.contains(&next_host) {
+            return true;
+        }
+
+        if self.trust_subdomains && next_host.ends_with(&format!(".{}", previous_host)) {
+            return true;
+        }
+
+        #[cfg(feature = "psl")]
+        {
+            if self.trust_registrable_domain {
+                let next_domain = psl::domain_str(&next_host);
+                if next_domain.is_some() && next_domain == psl::domain_str(&previous_host) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl Default for SensitiveHeaders {
+    fn default() -> SensitiveHeaders {
+        SensitiveHeaders::empty()
+            .header(AUTHORIZATION)
+            .header(COOKIE)
+            .header(HeaderName::from_static("cookie2"))
+            .header(PROXY_AUTHORIZATION)
+            .header(WWW_AUTHENTICATE)
+    }
+}
+
 impl<'a> Attempt<'a> {
     /// Get the type of redirect.
     pub fn status(&self) -> StatusCode {
         self.status
     }
 
+    /// Get the headers of the redirect response.
+    pub fn headers(&self) -> &HeaderMap {
+        self.headers
+    }
+
     /// Get the next URL to redirect to.
     pub fn url(&self) -> &Url {
         self.next
@@ -177,6 +515,7 @@ impl<'a> Attempt<'a> {
     pub fn previous(&self) -> &[Url] {
         self.previous
     }
+
     /// Returns an action meaning reqwest should follow the next URL.
     pub fn follow(self) -> Action {
         Action {
@@ -203,15 +542,116 @@ impl<'a> Attempt<'a> {
     }
 }
 
+impl AsyncAttempt {
+    /// Get the type of redirect.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the redirect response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the next URL to redirect to.
+    pub fn url(&self) -> &Url {
+        &self.next
+    }
+
+    /// Get the list of previous URLs that have already been requested in this chain.
+    pub fn previous(&self) -> &[Url] {
+        &self.previous
+    }
+
+    /// Get the headers that will be sent with the next request.
+    pub fn request_headers(&self) -> &HeaderMap {
+        &self.request_headers
+    }
+
+    /// Get a mutable reference to the headers that will be sent with the
+    /// next request.
+    pub fn request_headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.request_headers
+    }
+
+    /// Returns an action meaning reqwest should follow the next URL, sending
+    /// the request headers of this attempt.
+    pub fn follow(self) -> Action {
+        Action {
+            inner: ActionKind::FollowWith(self.request_headers),
+        }
+    }
+
+    /// Returns an action meaning reqwest should not follow the next URL.
+    ///
+    /// The 30x response will be returned as the `Ok` result.
+    pub fn stop(self) -> Action {
+        Action {
+            inner: ActionKind::Stop,
+        }
+    }
+
+    /// Returns an action failing the redirect with an error.
+    ///
+    /// The `Error` will be returned for the result of the sent request.
+    pub fn error<E: Into<Box<dyn StdError + Send + Sync>>>(self, error: E) -> Action {
+        Action {
+            inner: ActionKind::Error(error.into()),
+        }
+    }
+
+    fn as_attempt(&self) -> Attempt<'_> {
+        Attempt {
+            status: self.status,
+            headers: &self.headers,
+            next: &self.next,
+            previous: &self.previous,
+        }
+    }
+}
+
+impl Hop {
+    pub(crate) fn new(url: Url, status: StatusCode, headers: HeaderMap) -> Hop {
+        Hop {
+            url,
+            status,
+            headers,
+        }
+    }
+
+    /// Get the URL that was requested.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the status code of the redirect response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the redirect response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = Action> + Send>>;
+
 enum PolicyKind {
     Custom(Box<dyn Fn(Attempt) -> Action + Send + Sync + 'static>),
+    Async(Box<dyn Fn(AsyncAttempt) -> BoxFuture + Send + Sync + 'static>),
     Limit(usize),
     None,
 }
 
 impl fmt::Debug for Policy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_tuple("Policy").field(&self.inner).finish()
+        let mut builder = f.debug_tuple("Policy");
+        builder.field(&self.inner);
+        if self.sensitive != SensitiveHeaders::default() {
+            builder.field(&self.sensitive);
+        }
+        builder.finish()
     }
 }
 
@@ -219,6 +659,7 @@ impl fmt::Debug for PolicyKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             PolicyKind::Custom(..) => f.pad("Custom"),
+            PolicyKind::Async(..) => f.pad("Async"),
             PolicyKind::Limit(max) => f.debug_tuple("Limit").field(&max).finish(),
             PolicyKind::None => f.pad("None"),
         }
@@ -230,24 +671,11 @@ impl fmt::Debug for PolicyKind {
 #[derive(Debug)]
 pub(crate) enum ActionKind {
     Follow,
+    FollowWith(HeaderMap),
     Stop,
     Error(Box<dyn StdError + Send + Sync>),
 }
 
-pub(crate) fn remove_sensitive_headers(headers: &mut HeaderMap, next: &Url, previous: &[Url]) {
-    if let Some(previous) = previous.last() {
-        let cross_host = next.host_str() != previous.host_str()
-            || next.port_or_known_default() != previous.port_or_known_default();
-        if cross_host {
-            headers.remove(AUTHORIZATION);
-            headers.remove(COOKIE);
-            headers.remove("cookie2");
-            headers.remove(PROXY_AUTHORIZATION);
-            headers.remove(WWW_AUTHENTICATE);
-        }
-    }
-}
-
 #[derive(Debug)]
 struct TooManyRedirects;
 
@@ -268,14 +696,14 @@ fn test_redirect_policy_limit() {
         .collect::<Vec<_>>();
 
 
-    match policy.check(StatusCode::FOUND, &next, &previous) {
+    match policy.check(StatusCode::FOUND, &HeaderMap::new(), &next, &previous) {
         ActionKind::Follow => (),
         other => panic!("unexpected {:?}", other),
     }
 
     previous.push(Url::parse("http://a.b.d/e/33").unwrap());
 
-    match policy.check(StatusCode::FOUND, &next, &previous) {
+    match policy.check(StatusCode::FOUND, &HeaderMap::new(), &next, &previous) {
         ActionKind::Error(err) if err.is::<TooManyRedirects>() => (),
         other => panic!("unexpected {:?}", other),
     }
@@ -292,13 +720,13 @@ fn test_redirect_policy_custom() {
     });
 
     let next = Url::parse("http://bar/baz").unwrap();
-    match policy.check(StatusCode::FOUND, &next, &[]) {
+    match policy.check(StatusCode::FOUND, &HeaderMap::new(), &next, &[]) {
         ActionKind::Follow => (),
         other => panic!("unexpected {:?}", other),
     }
 
     let next = Url::parse("http://foo/baz").unwrap();
-     match policy.check(StatusCode::FOUND, &next, &[]) {
+     match policy.check(StatusCode::FOUND, &HeaderMap::new(), &next, &[]) {
         ActionKind::Stop => (),
         other => panic!("unexpected {:?}", other),
     }
@@ -317,13 +745,74 @@ fn test_remove_sensitive_headers() {
     let mut prev = vec![Url::parse("http://initial-domain.com/new_path").unwrap()];
     let mut filtered_headers = headers.clone();
 
-    remove_sensitive_headers(&mut headers, &next, &prev);
+    SensitiveHeaders::default().remove(&mut headers, &next, &prev);
     assert_eq!(headers, filtered_headers);
 
     prev.push(Url::parse("http://new-domain.com/path").unwrap());
     filtered_headers.remove(AUTHORIZATION);
     filtered_headers.remove(COOKIE);
 
-    remove_sensitive_headers(&mut headers, &next, &prev);
+    SensitiveHeaders::default().remove(&mut headers, &next, &prev);
     assert_eq!(headers, filtered_headers);
 }
+
+#[test]
+fn test_sensitive_headers_custom_header() {
+    use hyper::header::{HeaderValue, AUTHORIZATION};
+
+    let api_key = HeaderName::from_static("x-api-key");
+    let sensitive = SensitiveHeaders::default()
+        .header(api_key.clone())
+        .remove_header(&AUTHORIZATION);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("let me in"));
+    headers.insert(api_key.clone(), HeaderValue::from_static("secret"));
+
+    let next = Url::parse("http://new-domain.com/path").unwrap();
+    let prev = vec![Url::parse("http://initial-domain.com/path").unwrap()];
+
+    sensitive.remove(&mut headers, &next, &prev);
+    assert!(headers.contains_key(AUTHORIZATION));
+    assert!(!headers.contains_key(api_key));
+}
+
+#[test]
+fn test_sensitive_headers_trust_rules() {
+    let sensitive = SensitiveHeaders::default();
+    let trusted = |sensitive: &SensitiveHeaders, prev: &str, next: &str| {
+        sensitive.is_trusted(&Url::parse(next).unwrap(), &Url::parse(prev).unwrap())
+    };
+
+    assert!(trusted(&sensitive, "https://a.com/", "https://a.com/b"));
+    assert!(trusted(&sensitive, "https://a.com/", "http://a.com:443/"));
+    assert!(!trusted(&sensitive, "https://a.com/", "https://a.com:8443/"));
+    assert!(!trusted(&sensitive, "https://a.com/", "https://api.a.com/"));
+
+    let sensitive = SensitiveHeaders::default().strip_on_scheme_downgrade(true);
+    assert!(!trusted(&sensitive, "https://a.com/", "http://a.com:443/"));
+    assert!(trusted(&sensitive, "http://a.com/", "https://a.com:80/"));
+
+    let sensitive = SensitiveHeaders::default().trust_subdomains(true);
+    assert!(trusted(&sensitive, "https://a.com/", "https://api.a.com/"));
+    assert!(!trusted(&sensitive, "https://api.a.com/", "https://a.com/"));
+    assert!(!trusted(&sensitive, "https://a.com/", "https://evila.com/"));
+
+    let sensitive = SensitiveHeaders::default().trust_host("Auth.Example.com");
+    assert!(trusted(&sensitive, "https://a.com/", "https://auth.example.com:8443/"));
+    assert!(!trusted(&sensitive, "https://a.com/", "https://example.com/"));
+}
+
+#[cfg(feature = "psl")]
+#[test]
+fn test_sensitive_headers_trust_registrable_domain() {
+    let sensitive = SensitiveHeaders::default().trust_same_registrable_domain(true);
+    let trusted = |prev: &str, next: &str| {
+        sensitive.is_trusted(&Url::parse(next).unwrap(), &Url::parse(prev).unwrap())
+    };
+
+    assert!(trusted("https://a.example.co.uk/", "https://b.example.co.uk/"));
+    assert!(trusted("https://api.example.com/", "https://example.com/"));
+    assert!(!trusted("https://example.co.uk/", "https://other.co.uk/"));
+    assert!(!trusted("https://a.github.io/", "https://b.github.io/"));
+}
@@ -0,0 +1,81 @@
+//! Signing requests.
+//!
+//! A [`RequestSigner`] given to [`ClientBuilder::signer`] sees every request
+//! the way it is sent: after the client added its default headers and
+//! cookies, and with its final body. It signs again each redirected request.
+//!
+//! With the `aws-sigv4` feature, [`AwsSigV4`] signs requests with AWS
+//! Signature Version 4.
+//!
+//! [`ClientBuilder::signer`]: ../struct.ClientBuilder.html#method.signer
+
+use std::error::Error as StdError;
+
+#[cfg(any(feature = "__tls", feature = "aws-sigv4"))]
+use sha2::{Digest, Sha256};
+
+use crate::Request;
+
+#[cfg(feature = "aws-sigv4")]
+mod aws;
+
+#[cfg(feature = "aws-sigv4")]
+pub use self::aws::AwsSigV4;
+
+/// The payload hash of requests whose bodies are streams, which are not
+/// hashed.
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// The SHA-256 hash of an empty body.
+const EMPTY_PAYLOAD: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Signs requests before they are sent.
+pub trait RequestSigner: Send + Sync {
+    /// Signs `req`, usually by adding headers.
+    ///
+    /// `payload_hash` is the hex encoded SHA-256 hash of the body, or
+    /// [`UNSIGNED_PAYLOAD`] for streams. Without a TLS feature or the
+    /// `aws-sigv4` feature, which bring SHA-256, only empty bodies are
+    /// hashed, and others are unsigned payloads too.
+    ///
+    /// [`UNSIGNED_PAYLOAD`]: constant.UNSIGNED_PAYLOAD.html
+    fn sign(
+        &self,
+        req: &mut Request,
+        payload_hash: &str,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>>;
+}
+
+/// Signs `req` with `signer`, hashing its body.
+pub(crate) fn sign(
+    signer: &dyn RequestSigner,
+    req: &mut Request,
+) -> Result<(), Box<dyn StdError + Send + Sync>> {
+    let payload_hash = match req.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => payload_hash(bytes),
+            None => UNSIGNED_PAYLOAD.to_owned(),
+        },
+        None => EMPTY_PAYLOAD.to_owned(),
+    };
+    signer.sign(req, &payload_hash)
+}
+
+#[cfg(any(feature = "__tls", feature = "aws-sigv4"))]
+fn payload_hash(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+#[cfg(not(any(feature = "__tls", feature = "aws-sigv4")))]
+fn payload_hash(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        EMPTY_PAYLOAD.to_owned()
+    } else {
+        UNSIGNED_PAYLOAD.to_owned()
+    }
+}
+
+#[cfg(any(feature = "__tls", feature = "aws-sigv4"))]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,364 @@
+//! AWS Signature Version 4.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+
+use super::{hex, RequestSigner, UNSIGNED_PAYLOAD};
+use crate::Request;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// All but the unreserved characters, encoded in canonical requests.
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Signs requests to AWS services with Signature Version 4.
+///
+/// The signature covers the method, URL, payload hash and the `Host`,
+/// `Content-Type`, `Content-MD5`, `Range` and `x-amz-*` headers of requests.
+/// Bodies that are streams are signed as `UNSIGNED-PAYLOAD`, which only some
+/// services, like S3, accept.
+///
+/// # Example
+///
+/// ```
+/// # fn run() -> Result<(), reqwest::Error> {
+/// use std::sync::Arc;
+/// use reqwest::sign::AwsSigV4;
+///
+/// let signer = AwsSigV4::new("AKIDEXAMPLE", "secret", "eu-west-1", "s3");
+/// let client = reqwest::Client::builder()
+///     .signer(Arc::new(signer))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AwsSigV4 {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    region: String,
+    service: String,
+}
+
+impl AwsSigV4 {
+    /// Signs requests to `service` in `region` with an access key.
+    pub fn new(
+        access_key_id: &str,
+        secret_access_key: &str,
+        region: &str,
+        service: &str,
+    ) -> AwsSigV4 {
+        AwsSigV4 {
+            access_key_id: access_key_id.to_owned(),
+            secret_access_key: secret_access_key.to_owned(),
+            session_token: None,
+            region: region.to_owned(),
+            service: service.to_owned(),
+        }
+    }
+
+    /// Sends the `token` of temporary credentials, in the
+    /// `x-amz-security-token` header.
+    pub fn session_token(mut self, token: &str) -> AwsSigV4 {
+        self.session_token = Some(token.to_owned());
+        self
+    }
+
+    fn sign_at(
+        &self,
+        req: &mut Request,
+        payload_hash: &str,
+        now: SystemTime,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        let timestamp = timestamp(now);
+        let date = &timestamp[..8];
+
+        let headers = req.headers_mut();
+        headers.insert("x-amz-date", HeaderValue::from_str(&timestamp)?);
+        if let Some(ref token) = self.session_token {
+            let mut value = HeaderValue::from_str(token)?;
+            value.set_sensitive(true);
+            headers.insert("x-amz-security-token", value);
+        }
+        // S3 requires the hash, which also tells it about unsigned payloads.
+        if self.service == "s3" || payload_hash == UNSIGNED_PAYLOAD {
+            headers.insert("x-amz-content-sha256", HeaderValue::from_str(payload_hash)?);
+        }
+
+        let (canonical_request, signed_headers) =
+            canonical_request(req, payload_hash, self.service == "s3")?;
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            ALGORITHM,
+            timestamp,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes())),
+        );
+
+        let key = format!("AWS4{}", self.secret_access_key);
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, self.service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            ALGORITHM, self.access_key_id, scope, signed_headers, signature,
+        );
+        let mut authorization = HeaderValue::from_str(&authorization)?;
+        authorization.set_sensitive(true);
+        req.headers_mut().insert(AUTHORIZATION, authorization);
+        Ok(())
+    }
+}
+
+impl RequestSigner for AwsSigV4 {
+    fn sign(
+        &self,
+        req: &mut Request,
+        payload_hash: &str,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        self.sign_at(req, payload_hash, SystemTime::now())
+    }
+}
+
+impl fmt::Debug for AwsSigV4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AwsSigV4")
+            .field("access_key_id", &self.access_key_id)
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+/// Returns the canonical request of `req`, and its signed headers.
+fn canonical_request(
+    req: &Request,
+    payload_hash: &str,
+    s3: bool,
+) -> Result<(String, String), Box<dyn StdError + Send + Sync>> {
+    let url = req.url();
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => return Err("URL without a host".into()),
+    };
+
+    let mut headers = vec![("host".to_owned(), host)];
+    for name in req.headers().keys() {
+        if !is_signed(name) {
+            continue;
+        }
+        let mut values = Vec::new();
+        for value in req.headers().get_all(name) {
+            let value = value.to_str()?;
+            values.push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        headers.push((name.as_str().to_owned(), values.join(",")));
+    }
+    headers.sort();
+
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| &name[..])
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        req.method(),
+        canonical_path(url.path(), s3),
+        canonical_query(url.query().unwrap_or("")),
+        canonical_headers,
+        signed_headers,
+        payload_hash,
+    );
+    Ok((canonical_request, signed_headers))
+}
+
+fn is_signed(name: &HeaderName) -> bool {
+    match name.as_str() {
+        "content-type" | "content-md5" | "range" => true,
+        name => name.starts_with("x-amz-"),
+    }
+}
+
+/// Encodes the segments of `path` the canonical way, twice except for S3.
+fn canonical_path(path: &str, s3: bool) -> String {
+    if path.is_empty() {
+        return "/".to_owned();
+    }
+    path.split('/')
+        .map(|segment| {
+            let decoded = percent_decode_str(segment).collect::<Vec<u8>>();
+            let encoded = percent_encode(&decoded, UNRESERVED).to_string();
+            if s3 {
+                encoded
+            } else {
+                percent_encode(encoded.as_bytes(), UNRESERVED).to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Sorts the parameters of `query`, encoding them the canonical way.
+fn canonical_query(query: &str) -> String {
+    let encode = |s: &str| {
+        let decoded = percent_decode_str(s).collect::<Vec<u8>>();
+        percent_encode(&decoded, UNRESERVED).to_string()
+    };
+    let mut params = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let mut parts = param.splitn(2, '=');
+            let key = encode(parts.next().unwrap_or(""));
+            let value = encode(parts.next().unwrap_or(""));
+            (key, value)
+        })
+        .collect::<Vec<_>>();
+    params.sort();
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Formats `time` like `20150830T123600Z`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .expect("time after the epoch")
+        .as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // The civil date of days since 1970-01-01, from Howard Hinnant's
+    // `civil_from_days`.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+    )
+}
+
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(msg);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // From the AWS Signature Version 4 test suite.
+    const EMPTY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn signed(req: &mut Request, service: &str) -> String {
+        let signer = AwsSigV4::new(
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            service,
+        );
+        let now = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        signer.sign_at(req, EMPTY_HASH, now).unwrap();
+        assert_eq!(req.headers()["x-amz-date"], "20150830T123600Z");
+        req.headers()[AUTHORIZATION].to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_get_vanilla() {
+        let mut req = Request::new(
+            http::Method::GET,
+            "https://example.amazonaws.com/".parse().unwrap(),
+        );
+        assert_eq!(
+            signed(&mut req, "service"),
+            "AWS4-HMAC-SHA256 \
+             Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_get_with_query_and_content_type() {
+        let mut req = Request::new(
+            http::Method::GET,
+            "https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers"
+                .parse()
+                .unwrap(),
+        );
+        req.headers_mut().insert(
+            "content-type",
+            HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8"),
+        );
+        req.headers_mut()
+            .insert("user-agent", HeaderValue::from_static("not signed"));
+        assert_eq!(
+            signed(&mut req, "iam"),
+            "AWS4-HMAC-SHA256 \
+             Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn test_canonical_path_and_query() {
+        assert_eq!(canonical_path("", false), "/");
+        assert_eq!(
+            canonical_path("/documents%20and%20settings/a*b", false),
+            "/documents%2520and%2520settings/a%252Ab"
+        );
+        assert_eq!(
+            canonical_path("/documents%20and%20settings/a*b", true),
+            "/documents%20and%20settings/a%2Ab"
+        );
+        assert_eq!(canonical_query("b=2&a=%7e+x&a=1&c"), "a=1&a=~%2Bx&b=2&c=");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_164_800 + 3_723);
+        assert_eq!(timestamp(leap_day), "20240229T010203Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000Z");
+    }
+}
@@ -0,0 +1,224 @@
+//! Decoding of PKCS #12 archives and encrypted PKCS #8 keys, for rustls.
+//!
+//! native-tls hands these to the platform; rustls only takes plain DER, so
+//! the decryption is done here.
+
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, InnerIvInit, KeyIvInit};
+use cms::content_info::ContentInfo;
+use cms::encrypted_data::EncryptedData;
+use der::asn1::{Any, ContextSpecific, ObjectIdentifier, OctetString};
+use der::{Decode, Encode};
+use hmac::{Hmac, Mac};
+use ::pkcs12::cert_type::CertBag;
+use ::pkcs12::kdf::{derive_key_utf8, Pkcs12KeyType};
+use ::pkcs12::mac_data::MacData;
+use ::pkcs12::pbe_params::{EncryptedPrivateKeyInfo, Pkcs12PbeParams};
+use ::pkcs12::pfx::Pfx;
+use ::pkcs12::safe_bag::{SafeBag, SafeContents};
+use pkcs8::pkcs5::EncryptionScheme;
+use pkcs8::spki::AlgorithmIdentifierOwned;
+use sha1::Sha1;
+use sha2::Sha256;
+
+const DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ENCRYPTED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.6");
+const PBES2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.5.13");
+const LOCAL_KEY_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.21");
+const SHA_1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.14.3.2.26");
+const SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+
+/// Decodes a DER encoded PKCS #12 archive into a DER encoded PKCS #8
+/// private key and the certificates stored with it, leaf first.
+pub(super) fn decode(der: &[u8], password: &str) -> crate::Result<(Vec<u8>, Vec<Vec<u8>>)> {
+    let pfx = Pfx::from_der(der).map_err(crate::error::builder)?;
+    if pfx.auth_safe.content_type != DATA {
+        return Err(crate::error::builder(
+            "PKCS #12 archives in public-key integrity mode are not supported",
+        ));
+    }
+    let auth_safe = octets(&pfx.auth_safe.content)?;
+    if let Some(mac_data) = &pfx.mac_data {
+        verify_mac(mac_data, &auth_safe, password)?;
+    }
+
+    let mut key = None;
+    let mut key_id = None;
+    let mut certs = Vec::new();
+    for info in Vec::<ContentInfo>::from_der(&auth_safe).map_err(crate::error::builder)? {
+        let bags = if info.content_type == DATA {
+            octets(&info.content)?
+        } else if info.content_type == ENCRYPTED_DATA {
+            let data = info
+                .content
+                .decode_as::<EncryptedData>()
+                .map_err(crate::error::builder)?;
+            let ciphertext = data
+                .enc_content_info
+                .encrypted_content
+                .ok_or_else(|| crate::error::builder("PKCS #12 encrypted data is empty"))?;
+            decrypt(
+                &data.enc_content_info.content_enc_alg,
+                ciphertext.as_bytes(),
+                password,
+            )?
+        } else {
+            continue;
+        };
+
+        for bag in SafeContents::from_der(&bags).map_err(crate::error::builder)? {
+            if bag.bag_id == ::pkcs12::PKCS_12_CERT_BAG_OID {
+                let cert = ContextSpecific::<CertBag>::from_der(&bag.bag_value)
+                    .map_err(crate::error::builder)?
+                    .value;
+                if cert.cert_id != ::pkcs12::PKCS_12_X509_CERT_OID {
+                    continue;
+                }
+                certs.push((local_key_id(&bag), cert.cert_value.into_bytes()));
+            } else if bag.bag_id == ::pkcs12::PKCS_12_PKCS8_KEY_BAG_OID {
+                let info = ContextSpecific::<EncryptedPrivateKeyInfo>::from_der(&bag.bag_value)
+                    .map_err(crate::error::builder)?
+                    .value;
+                key = Some(decrypt(
+                    &info.encryption_algorithm,
+                    info.encrypted_data.as_bytes(),
+                    password,
+                )?);
+                key_id = local_key_id(&bag);
+            } else if bag.bag_id == ::pkcs12::PKCS_12_KEY_BAG_OID {
+                let info = ContextSpecific::<Any>::from_der(&bag.bag_value)
+                    .map_err(crate::error::builder)?
+                    .value;
+                key = Some(info.to_der().map_err(crate::error::builder)?);
+                key_id = local_key_id(&bag);
+            }
+        }
+    }
+
+    // The leaf certificate shares its `localKeyId` with the key.
+    if let Some(leaf) = certs
+        .iter()
+        .position(|(id, _)| id.is_some() && *id == key_id)
+    {
+        let leaf = certs.remove(leaf);
+        certs.insert(0, leaf);
+    }
+    let certs = certs.into_iter().map(|(_, cert)| cert).collect::<Vec<_>>();
+
+    match key {
+        Some(key) if !certs.is_empty() => Ok((key, certs)),
+        _ => Err(crate::error::builder(
+            "PKCS #12 archive does not contain a private key and certificate",
+        )),
+    }
+}
+
+/// Decrypts a DER encoded `EncryptedPrivateKeyInfo` into a DER encoded
+/// PKCS #8 private key.
+pub(super) fn decrypt_pkcs8(der: &[u8], password: &str) -> crate::Result<Vec<u8>> {
+    let info = EncryptedPrivateKeyInfo::from_der(der).map_err(crate::error::builder)?;
+    decrypt(&info.encryption_algorithm, info.encrypted_data.as_bytes(), password)
+}
+
+/// The value of the `localKeyId` attribute of a bag, which pairs a key with
+/// its certificate.
+fn local_key_id(bag: &SafeBag) -> Option<Any> {
+    bag.bag_attributes
+        .iter()
+        .flat_map(|attrs| attrs.iter())
+        .find(|attr| attr.oid == LOCAL_KEY_ID)
+        .and_then(|attr| attr.values.iter().next().cloned())
+}
+
+fn octets(content: &Any) -> crate::Result<Vec<u8>> {
+    content
+        .decode_as::<OctetString>()
+        .map(OctetString::into_bytes)
+        .map_err(crate::error::builder)
+}
+
+fn verify_mac(mac_data: &MacData, content: &[u8], password: &str) -> crate::Result<()> {
+    let salt = mac_data.mac_salt.as_bytes();
+    let expected = mac_data.mac.digest.as_bytes();
+    let verified = if mac_data.mac.algorithm.oid == SHA_1 {
+        let key = derive_key_utf8::<Sha1>(password, salt, Pkcs12KeyType::Mac, mac_data.iterations, 20)
+            .map_err(crate::error::builder)?;
+        let mut mac = Hmac::<Sha1>::new_from_slice(&key).map_err(crate::error::builder)?;
+        mac.update(content);
+        mac.verify_slice(expected).is_ok()
+    } else if mac_data.mac.algorithm.oid == SHA_256 {
+        let key = derive_key_utf8::<Sha256>(password, salt, Pkcs12KeyType::Mac, mac_data.iterations, 32)
+            .map_err(crate::error::builder)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key).map_err(crate::error::builder)?;
+        mac.update(content);
+        mac.verify_slice(expected).is_ok()
+    } else {
+        return Err(crate::error::builder("unsupported PKCS #12 MAC algorithm"));
+    };
+
+    if verified {
+        Ok(())
+    } else {
+        Err(crate::error::builder("PKCS #12 MAC verification failed, wrong password?"))
+    }
+}
+
+fn decrypt(
+    algorithm: &AlgorithmIdentifierOwned,
+    ciphertext: &[u8],
+    password: &str,
+) -> crate::Result<Vec<u8>> {
+    let params = algorithm
+        .parameters
+        .as_ref()
+        .ok_or_else(|| crate::error::builder("missing encryption parameters"))?
+        .to_der()
+        .map_err(crate::error::builder)?;
+
+    if algorithm.oid == PBES2 {
+        let params = pkcs8::pkcs5::pbes2::Parameters::from_der(&params)
+            .map_err(crate::error::builder)?;
+        return EncryptionScheme::from(params)
+            .decrypt(password, ciphertext)
+            .map_err(|_| crate::error::builder("decryption failed, wrong password?"));
+    }
+
+    // The PKCS #12 specific schemes, still the default of many tools.
+    let params = Pkcs12PbeParams::from_der(&params).map_err(crate::error::builder)?;
+    let derive = |id, len| {
+        derive_key_utf8::<Sha1>(
+            password,
+            params.salt.as_bytes(),
+            id,
+            params.iterations,
+            len,
+        )
+        .map_err(crate::error::builder)
+    };
+    let plaintext = if algorithm.oid == ::pkcs12::PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC {
+        let key = derive(Pkcs12KeyType::EncryptionKey, 24)?;
+        let iv = derive(Pkcs12KeyType::Iv, 8)?;
+        cbc::Decryptor::<des::TdesEde3>::new_from_slices(&key, &iv)
+            .map_err(crate::error::builder)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+    } else if algorithm.oid == ::pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC
+        || algorithm.oid == ::pkcs12::PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC
+    {
+        let bits = if algorithm.oid == ::pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC {
+            40
+        } else {
+            128
+        };
+        let key = derive(Pkcs12KeyType::EncryptionKey, bits / 8)?;
+        let iv = derive(Pkcs12KeyType::Iv, 8)?;
+        cbc::Decryptor::inner_iv_slice_init(rc2::Rc2::new_with_eff_key_len(&key, bits), &iv)
+            .map_err(crate::error::builder)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+    } else {
+        return Err(crate::error::builder(format!(
+            "unsupported encryption algorithm {}",
+            algorithm.oid
+        )));
+    };
+    plaintext.map_err(|_| crate::error::builder("decryption failed, wrong password?"))
+}
@@ -0,0 +1,897 @@
+//! Revocation checks against certificate revocation lists, and against
+//! OCSP responses stapled to a rustls handshake.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::signature;
+
+use super::{der_next, X509};
+use crate::error::BoxError;
+
+const SEQUENCE: u8 = 0x30;
+const BIT_STRING: u8 = 0x03;
+const OID: u8 = 0x06;
+
+const RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const RSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const RSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const RSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const EC: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+
+/// A certificate revocation list (CRL), as published by a certificate
+/// authority.
+///
+/// A list is only used once its signature verifies with the key of its
+/// issuer, which must be a root certificate added to the client or, with
+/// rustls, any trust anchor or a certificate presented by the server. Lists
+/// past their `nextUpdate` time fail the handshake.
+#[derive(Clone)]
+pub struct CertificateRevocationList {
+    /// The DER encoded `Name` of the authority that issued the list.
+    issuer: Vec<u8>,
+    /// The contents of the serial number `INTEGER`s of revoked certificates.
+    serials: Vec<Vec<u8>>,
+    /// The DER encoded `TBSCertList`, which the signature covers.
+    tbs: Vec<u8>,
+    /// The OID of the signature algorithm.
+    algorithm: Vec<u8>,
+    signature: Vec<u8>,
+    /// The `nextUpdate` time, in seconds since the Unix epoch.
+    next_update: Option<i64>,
+}
+
+impl CertificateRevocationList {
+    /// Create a `CertificateRevocationList` from a binary DER encoded CRL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn crl() -> Result<(), Box<std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my_crl.der")?
+    ///     .read_to_end(&mut buf)?;
+    /// let crl = reqwest::tls::CertificateRevocationList::from_der(&buf)?;
+    /// # drop(crl);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_der(der: &[u8]) -> crate::Result<CertificateRevocationList> {
+        parse_crl(der).ok_or_else(|| crate::error::builder("invalid certificate revocation list"))
+    }
+
+    /// Create a `CertificateRevocationList` from a PEM encoded CRL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn crl() -> Result<(), Box<std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my_crl.pem")?
+    ///     .read_to_end(&mut buf)?;
+    /// let crl = reqwest::tls::CertificateRevocationList::from_pem(&buf)?;
+    /// # drop(crl);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pem(pem: &[u8]) -> crate::Result<CertificateRevocationList> {
+        let der = super::pem_sections(pem)?
+            .into_iter()
+            .find(|(label, _)| label == "X509 CRL")
+            .map(|(_, der)| der)
+            .ok_or_else(|| crate::error::builder("no certificate revocation list found in PEM"))?;
+        CertificateRevocationList::from_der(&der)
+    }
+
+    fn is_signed_by(&self, spki: &[u8]) -> bool {
+        verify_signature(spki, &self.algorithm, &self.tbs, &self.signature)
+    }
+}
+
+impl fmt::Debug for CertificateRevocationList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CertificateRevocationList")
+            .field("revoked", &self.serials.len())
+            .finish()
+    }
+}
+
+fn parse_crl(der: &[u8]) -> Option<CertificateRevocationList> {
+    let (_, crl) = x509_parser::parse_x509_crl(der).ok()?;
+    Some(CertificateRevocationList {
+        issuer: crl.tbs_cert_list.issuer.as_raw().to_vec(),
+        serials: crl
+            .iter_revoked_certificates()
+            .map(|revoked| revoked.raw_serial().to_vec())
+            .collect(),
+        tbs: crl.tbs_cert_list.as_ref().to_vec(),
+        algorithm: crl.signature_algorithm.algorithm.as_bytes().to_vec(),
+        signature: crl.signature_value.data.to_vec(),
+        next_update: crl.next_update().map(|time| time.timestamp()),
+    })
+}
+
+/// The revocation lists configured with `ClientBuilder::add_crl`, and the
+/// certificates that may have issued them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RevocationLists {
+    crls: Vec<CertificateRevocationList>,
+    issuers: Vec<Issuer>,
+}
+
+#[derive(Clone, Debug)]
+struct Issuer {
+    /// The DER encoded subject `Name`.
+    subject: Vec<u8>,
+    /// The DER encoded `SubjectPublicKeyInfo`.
+    spki: Vec<u8>,
+}
+
+impl RevocationLists {
+    pub(crate) fn add(&mut self, crl: CertificateRevocationList) {
+        self.crls.push(crl);
+    }
+
+    /// Adds a DER encoded root certificate as a possible issuer of the lists.
+    #[cfg(any(feature = "native-tls-crate", test))]
+    pub(crate) fn add_issuer(&mut self, der: &[u8]) {
+        if let Some(cert) = X509::parse(der) {
+            self.issuers.push(Issuer {
+                subject: cert.subject.to_vec(),
+                spki: cert.spki.to_vec(),
+            });
+        }
+    }
+
+    /// Adds the trust anchors of rustls as possible issuers of the lists.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn add_trust_anchors(&mut self, roots: &rustls::RootCertStore) {
+        // Trust anchors only keep the contents of these sequences.
+        for root in &roots.roots {
+            let anchor = root.to_trust_anchor();
+            self.issuers.push(Issuer {
+                subject: super::der_encode(SEQUENCE, anchor.subject),
+                spki: super::der_encode(SEQUENCE, anchor.spki),
+            });
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.crls.is_empty()
+    }
+
+    /// Checks the DER encoded certificates presented by `host` against the
+    /// revocation lists. Certificates that cannot be parsed are skipped,
+    /// the handshake has already validated them.
+    pub(crate) fn verify<F>(&self, host: &str, chain: F) -> Result<(), BoxError>
+    where
+        F: FnOnce() -> Vec<Vec<u8>>,
+    {
+        if self.crls.is_empty() {
+            return Ok(());
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs() as i64)
+            .unwrap_or(0);
+        self.check(host, &chain(), now)
+    }
+
+    fn check(&self, host: &str, chain: &[Vec<u8>], now: i64) -> Result<(), BoxError> {
+        let certs = chain.iter().filter_map(|der| X509::parse(der)).collect::<Vec<_>>();
+        for cert in &certs {
+            for crl in self.crls.iter().filter(|crl| crl.issuer == cert.issuer) {
+                let signed = certs
+                    .iter()
+                    .filter(|issuer| issuer.subject == &crl.issuer[..])
+                    .map(|issuer| issuer.spki)
+                    .chain(
+                        self.issuers
+                            .iter()
+                            .filter(|issuer| issuer.subject == crl.issuer)
+                            .map(|issuer| &issuer.spki[..]),
+                    )
+                    .any(|spki| crl.is_signed_by(spki));
+                if !signed {
+                    return Err("certificate revocation list is not signed by its issuer".into());
+                }
+                if let Some(next_update) = crl.next_update {
+                    if next_update < now {
+                        return Err("certificate revocation list has expired".into());
+                    }
+                }
+                if crl.serials.iter().any(|serial| serial[..] == *cert.serial) {
+                    return Err(Box::new(crate::error::CertificateRevoked {
+                        host: host.to_owned(),
+                    }));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verifies `signature` over `message` with the key of a DER encoded
+/// `SubjectPublicKeyInfo`, for the signature algorithm with the OID
+/// `algorithm`.
+fn verify_signature(spki: &[u8], algorithm: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let parsed = (|| {
+        let spki = der_next(spki).filter(|el| el.tag == SEQUENCE)?;
+        let key_algorithm = der_next(spki.contents).filter(|el| el.tag == SEQUENCE)?;
+        let key = der_next(key_algorithm.rest)
+            .filter(|el| el.tag == BIT_STRING)
+            .and_then(|el| bit_string(el.contents))?;
+        let key_type = der_next(key_algorithm.contents).filter(|el| el.tag == OID)?;
+        let curve = der_next(key_type.rest)
+            .filter(|el| el.tag == OID)
+            .map_or(&[][..], |el| el.contents);
+        Some((key_type.contents, curve, key))
+    })();
+    let (key_type, curve, key) = match parsed {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+
+    let algorithm: &'static dyn signature::VerificationAlgorithm =
+        match (key_type, curve, algorithm) {
+            (EC, P256, ECDSA_SHA256) => &signature::ECDSA_P256_SHA256_ASN1,
+            (EC, P256, ECDSA_SHA384) => &signature::ECDSA_P256_SHA384_ASN1,
+            (EC, P384, ECDSA_SHA256) => &signature::ECDSA_P384_SHA256_ASN1,
+            (EC, P384, ECDSA_SHA384) => &signature::ECDSA_P384_SHA384_ASN1,
+            (RSA, _, RSA_SHA256) => &signature::RSA_PKCS1_2048_8192_SHA256,
+            (RSA, _, RSA_SHA384) => &signature::RSA_PKCS1_2048_8192_SHA384,
+            (RSA, _, RSA_SHA512) => &signature::RSA_PKCS1_2048_8192_SHA512,
+            _ => return false,
+        };
+    signature::UnparsedPublicKey::new(algorithm, key)
+        .verify(message, signature)
+        .is_ok()
+}
+
+/// Returns the bits of the contents of a `BIT STRING` without unused
+/// bits.
+fn bit_string(contents: &[u8]) -> Option<&[u8]> {
+    match contents.split_first() {
+        Some((&0, bits)) => Some(bits),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+pub(crate) use self::ocsp::{stapled_revocation, OcspVerifier};
+
+#[cfg(feature = "rustls-tls")]
+mod ocsp {
+    use std::error::Error as StdError;
+    use std::io;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use ring::digest;
+    use rustls::{RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError, WebPKIVerifier};
+    use tokio_rustls::webpki::DNSNameRef;
+
+    use super::{bit_string, verify_signature, BIT_STRING, OID, SEQUENCE};
+    use crate::error::BoxError;
+    use crate::tls::{der_encode, der_next, Der, X509};
+
+    const INTEGER: u8 = 0x02;
+    const OCTET_STRING: u8 = 0x04;
+    const ENUMERATED: u8 = 0x0a;
+    const GENERALIZED_TIME: u8 = 0x18;
+
+    const OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+    const OCSP_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
+    const EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+    const SHA_1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+    const SHA_256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+
+    /// How far the clocks of the client and the OCSP responder may differ.
+    const CLOCK_SKEW: u64 = 5 * 60;
+
+    /// Verifies server certificates like rustls does, and additionally
+    /// rejects end-entity certificates that a stapled OCSP response reports
+    /// as revoked.
+    ///
+    /// Handshakes without a stapled response, or with a response of unknown
+    /// status, are accepted.
+    pub(crate) struct OcspVerifier {
+        inner: WebPKIVerifier,
+    }
+
+    impl OcspVerifier {
+        pub(crate) fn new() -> OcspVerifier {
+            OcspVerifier {
+                inner: WebPKIVerifier::new(),
+            }
+        }
+    }
+
+    /// Ends the message of the handshake error for a certificate revoked by
+    /// its stapled response, as rustls only takes a string.
+    const STAPLED_REVOCATION: &str = ", according to the stapled OCSP response";
+
+    /// Replaces a handshake error for a certificate revoked by its stapled
+    /// response with a `CertificateRevoked`, like the one for revocation
+    /// lists.
+    pub(crate) fn stapled_revocation(err: BoxError) -> BoxError {
+        let host = {
+            let mut source: Option<&(dyn StdError + 'static)> = Some(&*err);
+            let mut host = None;
+            while let Some(err) = source {
+                let tls = match err.downcast_ref::<io::Error>() {
+                    Some(io) => io.get_ref().and_then(|err| err.downcast_ref::<TLSError>()),
+                    None => err.downcast_ref::<TLSError>(),
+                };
+                if let Some(TLSError::General(ref msg)) = tls {
+                    host = revoked_host(msg);
+                    break;
+                }
+                source = err.source();
+            }
+            host
+        };
+        match host {
+            Some(host) => Box::new(crate::error::CertificateRevoked { host }),
+            None => err,
+        }
+    }
+
+    fn revoked_host(msg: &str) -> Option<String> {
+        let revoked = msg.trim_end_matches(STAPLED_REVOCATION);
+        if revoked.len() == msg.len() {
+            return None;
+        }
+        let host = revoked
+            .trim_start_matches("certificate of ")
+            .trim_end_matches(" has been revoked");
+        Some(host.to_owned())
+    }
+
+    impl ServerCertVerifier for OcspVerifier {
+        fn verify_server_cert(
+            &self,
+            roots: &RootCertStore,
+            presented_certs: &[rustls::Certificate],
+            dns_name: DNSNameRef,
+            ocsp_response: &[u8],
+        ) -> Result<ServerCertVerified, TLSError> {
+            let verified =
+                self.inner
+                    .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)?;
+            if ocsp_response.is_empty() {
+                return Ok(verified);
+            }
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| TLSError::FailedToGetCurrentTime)?
+                .as_secs();
+            match check(roots, presented_certs, ocsp_response, now) {
+                Ok(Status::Good) | Ok(Status::Unknown) => Ok(verified),
+                Ok(Status::Revoked) => {
+                    let host: &str = dns_name.into();
+                    let revoked = crate::error::CertificateRevoked {
+                        host: host.to_owned(),
+                    };
+                    Err(TLSError::General(format!("{}{}", revoked, STAPLED_REVOCATION)))
+                }
+                Err(reason) => Err(TLSError::General(format!("invalid stapled OCSP response: {}", reason))),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Status {
+        Good,
+        Revoked,
+        Unknown,
+    }
+
+    /// Checks a DER encoded `OCSPResponse` for the end-entity certificate of
+    /// a verified chain.
+    fn check(
+        roots: &RootCertStore,
+        chain: &[rustls::Certificate],
+        response: &[u8],
+        now: u64,
+    ) -> Result<Status, &'static str> {
+        const MALFORMED: &str = "malformed response";
+
+        let leaf = chain
+            .first()
+            .and_then(|cert| X509::parse(&cert.0))
+            .ok_or("unparsable certificate")?;
+        let issuer_spki = issuer_spki(roots, chain, leaf.issuer).ok_or("issuer not found")?;
+
+        // OCSPResponse ::= SEQUENCE { responseStatus, responseBytes [0] EXPLICIT }
+        let response = der_next(response).filter(|el| el.tag == SEQUENCE).ok_or(MALFORMED)?;
+        let status = der_next(response.contents)
+            .filter(|el| el.tag == ENUMERATED)
+            .ok_or(MALFORMED)?;
+        if status.contents != [0] {
+            return Err("responder did not answer successfully");
+        }
+        let bytes = der_next(status.rest)
+            .filter(|el| el.tag == 0xa0)
+            .and_then(|el| der_next(el.contents))
+            .filter(|el| el.tag == SEQUENCE)
+            .ok_or(MALFORMED)?;
+        let response_type = der_next(bytes.contents).filter(|el| el.tag == OID).ok_or(MALFORMED)?;
+        if response_type.contents != OCSP_BASIC {
+            return Err("unsupported response type");
+        }
+
+        // BasicOCSPResponse ::= SEQUENCE {
+        //     tbsResponseData, signatureAlgorithm, signature, certs [0] EXPLICIT OPTIONAL }
+        let basic = der_next(response_type.rest)
+            .filter(|el| el.tag == OCTET_STRING)
+            .and_then(|el| der_next(el.contents))
+            .filter(|el| el.tag == SEQUENCE)
+            .ok_or(MALFORMED)?;
+        let basic = Signed::parse(basic.contents).ok_or(MALFORMED)?;
+        let certs = der_next(basic.rest)
+            .filter(|el| el.tag == 0xa0)
+            .and_then(|el| der_next(el.contents))
+            .filter(|el| el.tag == SEQUENCE)
+            .map_or(&[][..], |el| el.contents);
+
+        // The issuer signs the response itself, or delegates to a
+        // responder certificate it issued for OCSP signing.
+        let signed_by_issuer =
+            verify_signature(&issuer_spki, basic.algorithm, basic.data.element, basic.signature);
+        if !signed_by_issuer && !delegates(&issuer_spki, leaf.issuer, certs, &basic) {
+            return Err("not signed by the certificate issuer");
+        }
+
+        // ResponseData ::= SEQUENCE {
+        //     version [0] EXPLICIT DEFAULT v1, responderID, producedAt, responses, ... }
+        let mut fields = basic.data.contents;
+        if fields.first() == Some(&0xa0) {
+            fields = der_next(fields).ok_or(MALFORMED)?.rest;
+        }
+        let responder = der_next(fields).ok_or(MALFORMED)?;
+        let produced_at = der_next(responder.rest).ok_or(MALFORMED)?;
+        let responses = der_next(produced_at.rest)
+            .filter(|el| el.tag == SEQUENCE)
+            .ok_or(MALFORMED)?;
+
+        // SingleResponse ::= SEQUENCE {
+        //     certID, certStatus, thisUpdate, nextUpdate [0] EXPLICIT OPTIONAL, ... }
+        let mut rest = responses.contents;
+        while let Some(single) = der_next(rest) {
+            rest = single.rest;
+            let cert_id = der_next(single.contents)
+                .filter(|el| el.tag == SEQUENCE)
+                .ok_or(MALFORMED)?;
+            if !matches_cert_id(cert_id.contents, &leaf, &issuer_spki) {
+                continue;
+            }
+            let cert_status = der_next(cert_id.rest).ok_or(MALFORMED)?;
+            let this_update = der_next(cert_status.rest)
+                .filter(|el| el.tag == GENERALIZED_TIME)
+                .and_then(|el| generalized_time(el.contents).map(|time| (time, el.rest)));
+            let (this_update, rest) = this_update.ok_or(MALFORMED)?;
+            if this_update > now + CLOCK_SKEW {
+                return Err("response is not yet valid");
+            }
+            let next_update = der_next(rest)
+                .filter(|el| el.tag == 0xa0)
+                .and_then(|el| der_next(el.contents))
+                .filter(|el| el.tag == GENERALIZED_TIME);
+            if let Some(next_update) = next_update {
+                let next_update = generalized_time(next_update.contents).ok_or(MALFORMED)?;
+                if next_update + CLOCK_SKEW < now {
+                    return Err("response has expired");
+                }
+            }
+
+            return Ok(match cert_status.tag {
+                0x80 => Status::Good,
+                0xa1 => Status::Revoked,
+                _ => Status::Unknown,
+            });
+        }
+        Err("no response for the certificate")
+    }
+
+    /// The parts of a signed structure, like a certificate or a
+    /// `BasicOCSPResponse`.
+    struct Signed<'a> {
+        data: Der<'a>,
+        /// The OID of the signature algorithm.
+        algorithm: &'a [u8],
+        signature: &'a [u8],
+        /// The fields that follow the signature.
+        rest: &'a [u8],
+    }
+
+    impl<'a> Signed<'a> {
+        /// Splits the contents of a signed structure.
+        fn parse(contents: &'a [u8]) -> Option<Signed<'a>> {
+            let data = der_next(contents).filter(|el| el.tag == SEQUENCE)?;
+            let algorithm = der_next(data.rest).filter(|el| el.tag == SEQUENCE)?;
+            let signature = der_next(algorithm.rest).filter(|el| el.tag == BIT_STRING)?;
+            Some(Signed {
+                signature: bit_string(signature.contents)?,
+                algorithm: der_next(algorithm.contents).filter(|el| el.tag == OID)?.contents,
+                rest: signature.rest,
+                data,
+            })
+        }
+    }
+
+    /// Returns the DER encoded `SubjectPublicKeyInfo` of the certificate
+    /// named `issuer`, from the presented chain or the trust anchors.
+    fn issuer_spki(roots: &RootCertStore, chain: &[rustls::Certificate], issuer: &[u8]) -> Option<Vec<u8>> {
+        let intermediate = chain[1..]
+            .iter()
+            .filter_map(|cert| X509::parse(&cert.0))
+            .find(|cert| cert.subject == issuer)
+            .map(|cert| cert.spki.to_vec());
+        intermediate.or_else(|| {
+            // Trust anchors only keep the contents of these sequences.
+            roots
+                .roots
+                .iter()
+                .map(|root| root.to_trust_anchor())
+                .find(|anchor| der_encode(SEQUENCE, anchor.subject) == issuer)
+                .map(|anchor| der_encode(SEQUENCE, anchor.spki))
+        })
+    }
+
+    /// Whether any of the DER encoded `certs` was issued by the issuer of the
+    /// end-entity certificate for OCSP signing, and signed the response.
+    fn delegates(
+        issuer_spki: &[u8],
+        issuer: &[u8],
+        mut certs: &[u8],
+        response: &Signed,
+    ) -> bool {
+        while let Some(cert) = der_next(certs) {
+            certs = cert.rest;
+            let responder = match X509::parse(cert.element) {
+                Some(responder) => responder,
+                None => continue,
+            };
+            let issued = match Signed::parse(cert.contents) {
+                Some(cert) => {
+                    verify_signature(issuer_spki, cert.algorithm, cert.data.element, cert.signature)
+                        && has_ocsp_signing(cert.data.contents)
+                }
+                None => false,
+            };
+            if responder.issuer == issuer
+                && issued
+                && verify_signature(
+                    responder.spki,
+                    response.algorithm,
+                    response.data.element,
+                    response.signature,
+                )
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether the contents of a `TBSCertificate` carry an extended key usage
+    /// extension that allows OCSP signing.
+    fn has_ocsp_signing(mut tbs: &[u8]) -> bool {
+        while let Some(el) = der_next(tbs) {
+            tbs = el.rest;
+            if el.tag != 0xa3 {
+                continue;
+            }
+            let mut extensions = match der_next(el.contents).filter(|el| el.tag == SEQUENCE) {
+                Some(seq) => seq.contents,
+                None => return false,
+            };
+            while let Some(extension) = der_next(extensions) {
+                extensions = extension.rest;
+                let id = match der_next(extension.contents).filter(|el| el.tag == OID) {
+                    Some(id) if id.contents == EXT_KEY_USAGE => id,
+                    _ => continue,
+                };
+                // Skip the optional critical flag.
+                let mut value = der_next(id.rest);
+                if value.as_ref().map(|el| el.tag) == Some(0x01) {
+                    value = value.and_then(|el| der_next(el.rest));
+                }
+                let mut usages = match value
+                    .filter(|el| el.tag == OCTET_STRING)
+                    .and_then(|el| der_next(el.contents))
+                {
+                    Some(seq) => seq.contents,
+                    None => return false,
+                };
+                while let Some(usage) = der_next(usages) {
+                    if usage.contents == OCSP_SIGNING {
+                        return true;
+                    }
+                    usages = usage.rest;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether the contents of a `CertID` identify the end-entity
+    /// certificate.
+    fn matches_cert_id(cert_id: &[u8], leaf: &X509, issuer_spki: &[u8]) -> bool {
+        let parsed = (|| {
+            let algorithm = der_next(cert_id).filter(|el| el.tag == SEQUENCE)?;
+            let oid = der_next(algorithm.contents).filter(|el| el.tag == OID)?;
+            let name_hash = der_next(algorithm.rest).filter(|el| el.tag == OCTET_STRING)?;
+            let key_hash = der_next(name_hash.rest).filter(|el| el.tag == OCTET_STRING)?;
+            let serial = der_next(key_hash.rest).filter(|el| el.tag == INTEGER)?;
+            let key = der_next(issuer_spki)
+                .and_then(|spki| der_next(spki.contents))
+                .and_then(|alg| der_next(alg.rest))
+                .filter(|el| el.tag == BIT_STRING)
+                .and_then(|el| bit_string(el.contents))?;
+            Some((oid.contents, name_hash.contents, key_hash.contents, serial.contents, key))
+        })();
+        let (oid, name_hash, key_hash, serial, key) = match parsed {
+            Some(parsed) => parsed,
+            None => return false,
+        };
+
+        let hash = match oid {
+            SHA_1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            SHA_256 => &digest::SHA256,
+            _ => return false,
+        };
+        serial == leaf.serial
+            && digest::digest(hash, leaf.issuer).as_ref() == name_hash
+            && digest::digest(hash, key).as_ref() == key_hash
+    }
+
+    /// Parses a `GeneralizedTime` of the form `YYYYMMDDHHMMSS[.f]Z` into
+    /// seconds since the Unix epoch.
+    fn generalized_time(value: &[u8]) -> Option<u64> {
+        let value = std::str::from_utf8(value).ok()?;
+        if value.len() < 15 || !value.ends_with('Z') {
+            return None;
+        }
+        let num = |from: usize, to: usize| -> Option<u64> {
+            let digits = value.get(from..to)?;
+            if digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse().ok()
+            } else {
+                None
+            }
+        };
+        let (year, month, day) = (num(0, 4)?, num(4, 6)?, num(6, 8)?);
+        let (hour, minute, second) = (num(8, 10)?, num(10, 12)?, num(12, 14)?);
+        if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        // Days since the epoch of the proleptic Gregorian calendar, counting
+        // years from March so the leap day is last.
+        let (year, month) = if month <= 2 {
+            (year - 1, month + 9)
+        } else {
+            (year, month - 3)
+        };
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * month + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+        Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use crate::tls::revocation::tests::{
+            der, CA_PEM, LOCALHOST_CERT_PEM, LOCALHOST_OCSP, REVOKED_CERT_PEM, REVOKED_OCSP,
+        };
+
+        /// 2026-10-19, a day after the test responses were produced.
+        const NOW: u64 = 1_792_435_399;
+
+        fn check_stapled(cert_pem: &str, ocsp: &str, now: u64) -> Result<Status, &'static str> {
+            let mut roots = RootCertStore::empty();
+            roots.add(&rustls::Certificate(der(CA_PEM))).unwrap();
+            let chain = [rustls::Certificate(der(cert_pem))];
+            check(&roots, &chain, &base64::decode(ocsp).unwrap(), now)
+        }
+
+        #[test]
+        fn stapled_response_status() {
+            assert_eq!(check_stapled(LOCALHOST_CERT_PEM, LOCALHOST_OCSP, NOW), Ok(Status::Good));
+            assert_eq!(check_stapled(REVOKED_CERT_PEM, REVOKED_OCSP, NOW), Ok(Status::Revoked));
+            assert_eq!(
+                check_stapled(LOCALHOST_CERT_PEM, REVOKED_OCSP, NOW),
+                Err("no response for the certificate")
+            );
+            assert_eq!(
+                check_stapled(LOCALHOST_CERT_PEM, LOCALHOST_OCSP, 0),
+                Err("response is not yet valid")
+            );
+            assert_eq!(
+                check_stapled(LOCALHOST_CERT_PEM, LOCALHOST_OCSP, 10_000_000_000),
+                Err("response has expired")
+            );
+
+            // Flip a bit of the signed data.
+            let mut tampered = base64::decode(LOCALHOST_OCSP).unwrap();
+            tampered[60] ^= 1;
+            assert_eq!(
+                check_stapled(LOCALHOST_CERT_PEM, &base64::encode(&tampered), NOW),
+                Err("not signed by the certificate issuer")
+            );
+        }
+
+        #[test]
+        fn stapled_revocation_is_typed() {
+            let revoked = crate::error::CertificateRevoked {
+                host: "localhost".to_owned(),
+            };
+            let msg = format!("{}{}", revoked, STAPLED_REVOCATION);
+            let err = io::Error::new(io::ErrorKind::InvalidData, TLSError::General(msg));
+            let err = stapled_revocation(Box::new(err));
+            assert!(err.is::<crate::error::CertificateRevoked>());
+            assert_eq!(err.to_string(), "certificate of localhost has been revoked");
+
+            let err = io::Error::new(io::ErrorKind::InvalidData, TLSError::General("nope".into()));
+            assert!(stapled_revocation(Box::new(err)).is::<io::Error>());
+        }
+
+        #[test]
+        fn generalized_time_since_epoch() {
+            assert_eq!(generalized_time(b"19700101000000Z"), Some(0));
+            assert_eq!(generalized_time(b"20000229123000Z"), Some(951_827_400));
+            assert_eq!(generalized_time(b"20261018184319.5Z"), Some(1_792_348_999));
+            assert_eq!(generalized_time(b"20261018184319"), None);
+            assert_eq!(generalized_time(b"2026101818431+Z"), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) static CA_PEM: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBcDCCARegAwIBAgIUNNLNUcyeAnByyVW6BD80Pmqn0c8wCgYIKoZIzj0EAwIw
+DTELMAkGA1UEAwwCY2EwIBcNMjYxMDE4MTgxODAwWhgPMjEyNjA5MjQxODE4MDBa
+MA0xCzAJBgNVBAMMAmNhMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEJUFNA1XV
+PuE2UMTnt29a0OJv8JrEtwa7kivox+MOXEvy8r7Ss6HKdn18e0zbwR6vj661RB7i
+pz74oipaLrQO6qNTMFEwHQYDVR0OBBYEFDgfoCwCKU4+FO8xcS6WuuRKn4kUMB8G
+A1UdIwQYMBaAFDgfoCwCKU4+FO8xcS6WuuRKn4kUMA8GA1UdEwEB/wQFMAMBAf8w
+CgYIKoZIzj0EAwIDRwAwRAIgcK2GomMHL7b2MNTqKTJF4vnRCMuDLQrVozeUbl+E
+opICIC+w3agvXix/diQXB+XuaPt268pNNTjYCxrR65+42I7y
+-----END CERTIFICATE-----
+";
+
+    pub(super) static LOCALHOST_CERT_PEM: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBiDCCAS6gAwIBAgIUFb2gVuHCcI5wPR5BiUhQceYA7IAwCgYIKoZIzj0EAwIw
+DTELMAkGA1UEAwwCY2EwIBcNMjYxMDE4MTg0MzEzWhgPMjEyNjA5MjQxODQzMTNa
+MBQxEjAQBgNVBAMMCWxvY2FsaG9zdDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IA
+BHdlbB/VMrsET035VSUZ5K7nnYmzfaV7ZYxLZZrxz4CFCHYSrY99yYKDTzYVomvb
+P+Z3HJnp1umzxiZQGeB/KSOjYzBhMBQGA1UdEQQNMAuCCWxvY2FsaG9zdDAJBgNV
+HRMEAjAAMB0GA1UdDgQWBBRcA39xBXrNneC8SmXjsF65KKejWTAfBgNVHSMEGDAW
+gBQ4H6AsAilOPhTvMXEulrrkSp+JFDAKBggqhkjOPQQDAgNIADBFAiEAhZvyYOpp
+9GjxgLCv5fVvu0v/mt/0htyDzVQohNFrXg8CIESUKpYFreAsJ9YruT2/uOr3L+9g
+t9UC9RHUtx/VrhUu
+-----END CERTIFICATE-----
+";
+
+    pub(super) static REVOKED_CERT_PEM: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBiDCCAS6gAwIBAgIUas5KpjQnK1mAAB5PjJD+Fbf5zJwwCgYIKoZIzj0EAwIw
+DTELMAkGA1UEAwwCY2EwIBcNMjYxMDE4MTg0MzEzWhgPMjEyNjA5MjQxODQzMTNa
+MBQxEjAQBgNVBAMMCWxvY2FsaG9zdDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IA
+BFdpIrOOaEtGLAIYyIX2OtiHe0VLG77pTN39sP4rG7tMZzrP9DyDOuVvbc0DeJW+
+Pv6wqvNjT1cbXOwb/dJqTmqjYzBhMBQGA1UdEQQNMAuCCWxvY2FsaG9zdDAJBgNV
+HRMEAjAAMB0GA1UdDgQWBBTda7UyWL7WFw03Zh+XiTSsPXG2OjAfBgNVHSMEGDAW
+gBQ4H6AsAilOPhTvMXEulrrkSp+JFDAKBggqhkjOPQQDAgNIADBFAiEA43DfKr2C
+RJiHiHnA4hUfNWmU2FfbvnN+CK/SfMR/IZMCIDI0WfD8Njly/nnGRpnznsbo2MTf
+6Kkw++rCeXoUmU7/
+-----END CERTIFICATE-----
+";
+
+    pub(super) static CRL_PEM: &str = "\
+-----BEGIN X509 CRL-----
+MIHQMHcCAQEwCgYIKoZIzj0EAwIwDTELMAkGA1UEAwwCY2EXDTI2MTAxODE4NDMx
+OVoYDzIxMjYwOTI0MTg0MzE5WjAnMCUCFGrOSqY0JytZgAAeT4yQ/hW3+cycFw0y
+NjEwMTgxODQzMTlaoA4wDDAKBgNVHRQEAwIBATAKBggqhkjOPQQDAgNJADBGAiEA
+7XOwMbRQ7suUNdYkxO/JCxZCIOhvtWvvnPs3v9OlOFwCIQDINlVLoNaL/jji9TmE
+JAefbC10L6ChcxJ7Bg1lVxM2Fw==
+-----END X509 CRL-----
+";
+
+    pub(super) static LOCALHOST_OCSP: &str = "\
+        MIICjQoBAKCCAoYwggKCBgkrBgEFBQcwAQEEggJzMIICbzCBm6EPMA0xCzAJBgNVBAMMAmNhGA8yMDI2MTAxODE4\
+        NDMxOVowdzB1ME0wCQYFKw4DAhoFAAQUEQSM0gyzWSMH0Bn9CIlWK2LTZJIEFDgfoCwCKU4+FO8xcS6WuuRKn4kU\
+        AhQVvaBW4cJwjnA9HkGJSFBx5gDsgIAAGA8yMDI2MTAxODE4NDMxOVqgERgPMjEyNjA5MjQxODQzMTlaMAoGCCqG\
+        SM49BAMCA0cAMEQCIHSvhZn4mZzGaz6n+gACnQrKc+c43W8BSEx4qGjYOqyRAiAwgo4JDBO3DvJ55CT559L9i8fG\
+        ggXbq0E9wvzQZUNmBqCCAXgwggF0MIIBcDCCARegAwIBAgIUNNLNUcyeAnByyVW6BD80Pmqn0c8wCgYIKoZIzj0E\
+        AwIwDTELMAkGA1UEAwwCY2EwIBcNMjYxMDE4MTgxODAwWhgPMjEyNjA5MjQxODE4MDBaMA0xCzAJBgNVBAMMAmNh\
+        MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEJUFNA1XVPuE2UMTnt29a0OJv8JrEtwa7kivox+MOXEvy8r7Ss6HK\
+        dn18e0zbwR6vj661RB7ipz74oipaLrQO6qNTMFEwHQYDVR0OBBYEFDgfoCwCKU4+FO8xcS6WuuRKn4kUMB8GA1Ud\
+        IwQYMBaAFDgfoCwCKU4+FO8xcS6WuuRKn4kUMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgcK2G\
+        omMHL7b2MNTqKTJF4vnRCMuDLQrVozeUbl+EopICIC+w3agvXix/diQXB+XuaPt268pNNTjYCxrR65+42I7y";
+
+    pub(super) static REVOKED_OCSP: &str = "\
+        MIICoAoBAKCCApkwggKVBgkrBgEFBQcwAQEEggKGMIICgjCBrqEPMA0xCzAJBgNVBAMMAmNhGA8yMDI2MTAxODE4\
+        NDMxOVowgYkwgYYwTTAJBgUrDgMCGgUABBQRBIzSDLNZIwfQGf0IiVYrYtNkkgQUOB+gLAIpTj4U7zFxLpa65Eqf\
+        iRQCFGrOSqY0JytZgAAeT4yQ/hW3+cycoREYDzIwMjYxMDE4MTg0MzE5WhgPMjAyNjEwMTgxODQzMTlaoBEYDzIx\
+        MjYwOTI0MTg0MzE5WjAKBggqhkjOPQQDAgNHADBEAiAPoGDX9HIJumVegHuKQbRtpyUiIN8TeHXasRc/DDphAAIg\
+        eVUGcHneqPRU911GTA2uawRAfkUMiqm98uhnTkTnUnmgggF4MIIBdDCCAXAwggEXoAMCAQICFDTSzVHMngJwcslV\
+        ugQ/ND5qp9HPMAoGCCqGSM49BAMCMA0xCzAJBgNVBAMMAmNhMCAXDTI2MTAxODE4MTgwMFoYDzIxMjYwOTI0MTgx\
+        ODAwWjANMQswCQYDVQQDDAJjYTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABCVBTQNV1T7hNlDE57dvWtDib/Ca\
+        xLcGu5Ir6MfjDlxL8vK+0rOhynZ9fHtM28Eer4+utUQe4qc++KIqWi60DuqjUzBRMB0GA1UdDgQWBBQ4H6AsAilO\
+        PhTvMXEulrrkSp+JFDAfBgNVHSMEGDAWgBQ4H6AsAilOPhTvMXEulrrkSp+JFDAPBgNVHRMBAf8EBTADAQH/MAoG\
+        CCqGSM49BAMCA0cAMEQCIHCthqJjBy+29jDU6ikyReL50QjLgy0K1aM3lG5fhKKSAiAvsN2oL14sf3YkFwfl7mj7\
+        duvKTTU42Asa0eufuNiO8g==";
+
+    pub(super) fn der(pem: &str) -> Vec<u8> {
+        crate::tls::pem_sections(pem.as_bytes()).unwrap().remove(0).1
+    }
+
+    #[test]
+    fn crl_lists_revoked_certificates() {
+        let crl = CertificateRevocationList::from_pem(CRL_PEM.as_bytes()).unwrap();
+        assert_eq!(crl.serials.len(), 1);
+        let der_crl = CertificateRevocationList::from_der(&der(CRL_PEM)).unwrap();
+        assert_eq!(der_crl.issuer, crl.issuer);
+
+        let mut crls = RevocationLists::default();
+        crls.add(crl.clone());
+        crls.verify("localhost", || vec![der(LOCALHOST_CERT_PEM), der(CA_PEM)]).unwrap();
+        let err = crls
+            .verify("localhost", || vec![der(REVOKED_CERT_PEM), der(CA_PEM)])
+            .unwrap_err();
+        assert!(err.is::<crate::error::CertificateRevoked>());
+        assert_eq!(err.to_string(), "certificate of localhost has been revoked");
+
+        // The issuer may also be a root certificate that the server omits.
+        let mut roots = RevocationLists::default();
+        roots.add(crl.clone());
+        roots.add_issuer(&der(CA_PEM));
+        let err = roots.verify("localhost", || vec![der(REVOKED_CERT_PEM)]).unwrap_err();
+        assert!(err.is::<crate::error::CertificateRevoked>());
+
+        // Without the issuer, the list is not trusted.
+        let err = crls.verify("localhost", || vec![der(LOCALHOST_CERT_PEM)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "certificate revocation list is not signed by its issuer"
+        );
+
+        // The list is stale after its `nextUpdate`, in 2126.
+        let chain = [der(LOCALHOST_CERT_PEM), der(CA_PEM)];
+        let err = crls.check("localhost", &chain, 5_000_000_000).unwrap_err();
+        assert_eq!(err.to_string(), "certificate revocation list has expired");
+
+        // Flip a bit of the signature.
+        let mut tampered = crl;
+        tampered.signature[20] ^= 1;
+        let mut crls = RevocationLists::default();
+        crls.add(tampered);
+        let err = crls.verify("localhost", || chain.to_vec()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "certificate revocation list is not signed by its issuer"
+        );
+
+        CertificateRevocationList::from_pem(CA_PEM.as_bytes()).unwrap_err();
+        CertificateRevocationList::from_der(&der(CA_PEM)).unwrap_err();
+    }
+}
@@ -38,6 +38,27 @@ impl Body {
         }
     }
 
+    /// Converts the body into a `web_sys::Response` built with `init`.
+    pub(crate) fn to_js_response(
+        &self,
+        init: &web_sys::ResponseInit,
+    ) -> crate::Result<web_sys::Response> {
+        let js_resp = match &self.inner {
+            Inner::Bytes(body_bytes) => {
+                let body_bytes: &[u8] = body_bytes.as_ref();
+                let body_array: Uint8Array = body_bytes.into();
+                web_sys::Response::new_with_opt_buffer_source_and_init(Some(&body_array), init)
+            }
+            Inner::Multipart(form) => {
+                let form_data = form.to_form_data()?;
+                web_sys::Response::new_with_opt_form_data_and_init(Some(&form_data), init)
+            }
+        };
+        js_resp
+            .map_err(crate::error::wasm)
+            .map_err(crate::error::builder)
+    }
+
     #[inline]
     pub(crate) fn from_form(f: Form) -> Body {
         Self {
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::fmt;
 
 use bytes::Bytes;
@@ -5,6 +6,9 @@ use js_sys::Uint8Array;
 use http::{HeaderMap, StatusCode};
 use url::Url;
 
+use super::Body;
+use crate::response::ResponseUrl;
+
 #[cfg(feature = "json")]
 use serde::de::DeserializeOwned;
 
@@ -146,3 +150,35 @@ impl fmt::Debug for Response {
             .finish()
     }
 }
+
+impl<T: Into<Body>> TryFrom<http::Response<T>> for Response {
+    type Error = crate::Error;
+
+    fn try_from(r: http::Response<T>) -> crate::Result<Response> {
+        let (mut parts, body) = r.into_parts();
+
+        let js_headers = web_sys::Headers::new()
+            .map_err(crate::error::wasm)
+            .map_err(crate::error::builder)?;
+        for (name, value) in &parts.headers {
+            js_headers
+                .append(
+                    name.as_str(),
+                    value.to_str().map_err(crate::error::builder)?,
+                )
+                .map_err(crate::error::wasm)
+                .map_err(crate::error::builder)?;
+        }
+
+        let mut init = web_sys::ResponseInit::new();
+        init.status(parts.status.as_u16());
+        init.headers(&js_headers.into());
+        let js_resp = body.into().to_js_response(&init)?;
+
+        let url = match parts.extensions.remove::<ResponseUrl>() {
+            Some(url) => url.0,
+            None => Url::parse("http://no.url.provided.local").map_err(crate::error::builder)?,
+        };
+        Ok(Response::new(http::Response::from_parts(parts, js_resp), url))
+    }
+}
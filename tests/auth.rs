@@ -0,0 +1,208 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use reqwest::auth::Ntlm;
+
+/// A `CHALLENGE_MESSAGE` for Unicode strings, with a target info.
+fn ntlm_challenge() -> Vec<u8> {
+    let mut msg = b"NTLMSSP\0".to_vec();
+    msg.extend_from_slice(&[2, 0, 0, 0]);
+    msg.extend_from_slice(&[0, 0, 0, 0, 48, 0, 0, 0]);
+    msg.extend_from_slice(&0x0080_0201u32.to_le_bytes());
+    msg.extend_from_slice(b"\x01\x23\x45\x67\x89\xab\xcd\xef");
+    msg.extend_from_slice(&[0; 8]);
+    msg.extend_from_slice(&[8, 0, 8, 0, 48, 0, 0, 0]);
+    msg.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    msg
+}
+
+/// The message type of an NTLM token, and its domain and user names for an
+/// `AUTHENTICATE_MESSAGE`.
+fn ntlm_message(value: &str) -> (u8, String) {
+    let token = base64::decode(value.trim_start_matches("NTLM ")).unwrap();
+    assert!(token.starts_with(b"NTLMSSP\0"), "{:?}", value);
+    if token[8] != 3 {
+        return (token[8], String::new());
+    }
+    let field = |at: usize| {
+        let len = u16::from_le_bytes([token[at], token[at + 1]]) as usize;
+        let offset = u32::from_le_bytes([token[at + 4], token[at + 5], token[at + 6], token[at + 7]]);
+        let utf16 = token[offset as usize..offset as usize + len]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16(&utf16).unwrap()
+    };
+    (3, format!("{}\\{}", field(28), field(36)))
+}
+
+/// Reads a request, returning its head and body.
+fn read_request(tcp: &mut TcpStream) -> Option<(String, Vec<u8>)> {
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        match tcp.read(&mut byte) {
+            Ok(1) => head.push(byte[0]),
+            _ => return None,
+        }
+    }
+    let head = String::from_utf8(head).unwrap();
+    if header(&head, "transfer-encoding") == Some("chunked") {
+        let mut body = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            while !line.ends_with(b"\r\n") {
+                tcp.read_exact(&mut byte).unwrap();
+                line.push(byte[0]);
+            }
+            let size = std::str::from_utf8(&line).unwrap().trim();
+            let size = usize::from_str_radix(size, 16).unwrap();
+            let mut chunk = vec![0; size + 2];
+            tcp.read_exact(&mut chunk).unwrap();
+            if size == 0 {
+                return Some((head, body));
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+    }
+    let len = header(&head, "content-length").map_or(0, |len| len.parse().unwrap());
+    let mut body = vec![0; len];
+    tcp.read_exact(&mut body).unwrap();
+    Some((head, body))
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next()?;
+        if key.eq_ignore_ascii_case(name) {
+            Some(parts.next()?.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Spawns a server authenticating connections with NTLM, which echoes the
+/// user and body of requests on authenticated connections.
+fn ntlm_server() -> std::net::SocketAddr {
+    fn ok(user: &str, body: Vec<u8>) -> String {
+        let body = format!("{}: {}", user, String::from_utf8(body).unwrap());
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for tcp in listener.incoming() {
+            let mut tcp = tcp.unwrap();
+            std::thread::spawn(move || {
+                let mut negotiated = false;
+                let mut authenticated = None;
+                while let Some((head, body)) = read_request(&mut tcp) {
+                    let res = match header(&head, "authorization").map(ntlm_message) {
+                        None if authenticated.is_none() => "HTTP/1.1 401 Unauthorized\r\n\
+                                 WWW-Authenticate: Basic realm=\"corp\"\r\n\
+                                 WWW-Authenticate: NTLM\r\n\
+                                 Content-Length: 6\r\n\r\n\
+                                 denied"
+                            .to_owned(),
+                        Some((1, _)) => {
+                            negotiated = true;
+                            format!(
+                                "HTTP/1.1 401 Unauthorized\r\n\
+                                 WWW-Authenticate: NTLM {}\r\n\
+                                 Content-Length: 6\r\n\r\n\
+                                 denied",
+                                base64::encode(ntlm_challenge()),
+                            )
+                        }
+                        Some((3, user)) => {
+                            assert!(negotiated, "authenticated on the negotiating connection");
+                            authenticated = Some(user);
+                            ok(authenticated.as_ref().unwrap(), body)
+                        }
+                        None => ok(authenticated.as_ref().unwrap(), body),
+                        Some((kind, _)) => panic!("unexpected NTLM message {}", kind),
+                    };
+                    tcp.write_all(res.as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn ntlm_server_auth() {
+    let _ = env_logger::try_init();
+
+    let addr = ntlm_server();
+    let client = reqwest::Client::builder()
+        .auth(Arc::new(Ntlm::new("CORP\\alice", "secret")))
+        .build()
+        .unwrap();
+
+    let res = client
+        .post(&format!("http://{}/", addr))
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "CORP\\alice: hello");
+}
+
+#[cfg(feature = "stream")]
+#[tokio::test]
+async fn ntlm_server_auth_streamed_body() {
+    let addr = ntlm_server();
+    let client = reqwest::Client::builder()
+        .auth(Arc::new(Ntlm::new("alice", "secret")))
+        .build()
+        .unwrap();
+
+    // Bodies that can't be sent again aren't authenticated.
+    let body = reqwest::Body::wrap_stream(futures_util::stream::once(async {
+        Ok::<_, std::io::Error>("hello")
+    }));
+    let res = client
+        .post(&format!("http://{}/", addr))
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+    res.bytes().await.unwrap();
+
+    // Until a connection is authenticated, and pooled for the next request.
+    let res = client
+        .post(&format!("http://{}/", addr))
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "\\alice: hello");
+
+    let body = reqwest::Body::wrap_stream(futures_util::stream::once(async {
+        Ok::<_, std::io::Error>("again")
+    }));
+    let res = client
+        .post(&format!("http://{}/", addr))
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "\\alice: again");
+}
+
+#[tokio::test]
+async fn ntlm_server_without_auth() {
+    let addr = ntlm_server();
+
+    let res = reqwest::get(&format!("http://{}/", addr)).await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(res.text().await.unwrap(), "denied");
+}
@@ -314,3 +314,217 @@ async fn test_redirect_302_with_set_cookies() {
     assert_eq!(res.url().as_str(), dst);
     assert_eq!(res.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_redirect_keeps_request_extensions() {
+    #[derive(Debug, PartialEq)]
+    struct TraceId(u32);
+
+    let server = server::http(move |req| async move {
+        if req.uri() == "/302" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            assert_eq!(req.uri(), "/dst");
+            http::Response::default()
+        }
+    });
+
+    let url = format!("http://{}/302", server.addr());
+
+    let client = reqwest::Client::new();
+    let mut req = client.get(&url).build().unwrap();
+    req.extensions_mut().insert(TraceId(42));
+
+    let res = client.execute(req).await.unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.extensions().get::<TraceId>(), Some(&TraceId(42)));
+    assert!(res.remote_addr().is_some());
+}
+
+#[tokio::test]
+async fn test_redirect_history() {
+    let server = server::http(move |req| async move {
+        match req.uri().path() {
+            "/a" => http::Response::builder()
+                .status(301)
+                .header("location", "/b")
+                .body(Default::default())
+                .unwrap(),
+            "/b" => http::Response::builder()
+                .status(302)
+                .header("location", "/c")
+                .header("x-hop", "b")
+                .body(Default::default())
+                .unwrap(),
+            _ => {
+                assert_eq!(req.uri(), "/c");
+                http::Response::default()
+            }
+        }
+    });
+
+    let url = format!("http://{}/a", server.addr());
+    let res = reqwest::get(&url).await.unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.url().as_str(), format!("http://{}/c", server.addr()));
+
+    let history = res.redirect_history();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].url().as_str(), url);
+    assert_eq!(history[0].status(), reqwest::StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(history[0].headers()["location"], "/b");
+    assert_eq!(
+        history[1].url().as_str(),
+        format!("http://{}/b", server.addr())
+    );
+    assert_eq!(history[1].status(), reqwest::StatusCode::FOUND);
+    assert_eq!(history[1].headers()["x-hop"], "b");
+}
+
+#[tokio::test]
+async fn test_redirect_policy_sees_response_headers() {
+    let server = server::http(move |req| async move {
+        if req.uri() == "/start" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .header("retry-after", "120")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            panic!("redirect should have been stopped");
+        }
+    });
+
+    let policy = reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.headers().contains_key("retry-after") {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    });
+    let client = reqwest::Client::builder().redirect(policy).build().unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::FOUND);
+    assert!(res.redirect_history().is_empty());
+}
+
+#[tokio::test]
+async fn test_redirect_async_policy_rewrites_headers() {
+    let server = server::http(move |req| async move {
+        if req.uri() == "/start" {
+            assert_eq!(req.headers()["authorization"], "let me in");
+            let location = format!("http://localhost:{}/dst", req.headers()["host"]
+                .to_str()
+                .unwrap()
+                .rsplit(':')
+                .next()
+                .unwrap());
+            http::Response::builder()
+                .status(302)
+                .header("location", location)
+                .body(Default::default())
+                .unwrap()
+        } else {
+            assert_eq!(req.uri(), "/dst");
+            assert_eq!(req.headers()["authorization"], "trusted");
+            http::Response::default()
+        }
+    });
+
+    let policy = reqwest::redirect::Policy::custom_async(|mut attempt| async move {
+        // Sensitive headers were already removed for the cross-host hop.
+        assert!(!attempt.request_headers().contains_key("authorization"));
+        tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+        attempt.request_headers_mut().insert(
+            reqwest::header::AUTHORIZATION,
+            reqwest::header::HeaderValue::from_static("trusted"),
+        );
+        attempt.follow()
+    });
+    let client = reqwest::Client::builder().redirect(policy).build().unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client
+        .get(&url)
+        .header("authorization", "let me in")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.url().host_str(), Some("localhost"));
+}
+
+#[tokio::test]
+async fn test_redirect_async_policy_stop() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.uri(), "/start");
+        http::Response::builder()
+            .status(302)
+            .header("location", "/dst")
+            .body(Default::default())
+            .unwrap()
+    });
+
+    let policy = reqwest::redirect::Policy::custom_async(|attempt| async move {
+        tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+        attempt.stop()
+    });
+    let client = reqwest::Client::builder().redirect(policy).build().unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::FOUND);
+    assert_eq!(res.url().as_str(), url);
+}
+
+#[tokio::test]
+async fn test_redirect_looks_up_netrc_for_next_host() {
+    let end_server = server::http(move |req| async move {
+        // base64("bob:b")
+        assert_eq!(req.headers()["authorization"], "Basic Ym9iOmI=");
+        http::Response::default()
+    });
+
+    let end_port = end_server.addr().port();
+    let mid_server = server::http(move |req| async move {
+        // base64("alice:a")
+        assert_eq!(req.headers()["authorization"], "Basic YWxpY2U6YQ==");
+        http::Response::builder()
+            .status(302)
+            .header("location", format!("http://localhost:{}/end", end_port))
+            .body(Default::default())
+            .unwrap()
+    });
+
+    let netrc = std::env::temp_dir().join("reqwest-test-redirect-netrc");
+    std::fs::write(
+        &netrc,
+        "machine 127.0.0.1 login alice password a\nmachine localhost login bob password b\n",
+    )
+    .unwrap();
+
+    let url = format!("http://{}/start", mid_server.addr());
+    let res = reqwest::Client::builder()
+        .no_proxy()
+        .netrc(&netrc)
+        .build()
+        .unwrap()
+        .get(&url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.url().as_str(), format!("http://localhost:{}/end", end_port));
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
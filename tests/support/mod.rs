@@ -1,4 +1,7 @@
 pub mod server;
+#[cfg(feature = "rustls-tls")]
+#[allow(unused)]
+pub mod tls;
 
 // TODO: remove once done converting to new support server?
 #[allow(unused)]